@@ -0,0 +1,677 @@
+//! Command line operations, for when the UI isn't needed
+
+use std::{error::Error, path::PathBuf, time::Duration};
+
+use clap::{Args, Subcommand};
+
+use crate::nvapi::{
+    self, channels,
+    cuda::{self, CudaVersion},
+    legacy::Support,
+    release::DriverRelease,
+    resolve::Confidence,
+    scan::{self, MatrixEntry, ScanOptions},
+    source::{self, AjaxDriverService, DriverQuery, DriverSource, Fallback},
+    DetectedGpu, DeviceDatabase, Driver, DriverChannels, DriverPlatform, DriverVersion,
+    LookupTarget,
+};
+use crate::setup::{
+    self,
+    catalog::Catalog,
+    graph::DependencyGraph,
+    package::Package,
+    patch::{self, PatchRules},
+    quarantine::Quarantine,
+};
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Look for builds of a branch that aren't listed by NVIDIA, e.g. `scan 516`
+    Scan {
+        #[clap(value_parser)]
+        major: u16,
+        /// Requests in flight at once
+        #[clap(long, value_parser, default_value = "8")]
+        concurrency: usize,
+        /// Minimum time between two requests, in milliseconds
+        #[clap(long, value_parser, default_value = "100")]
+        delay: u64,
+    },
+    /// Probe which variants of a version exist, e.g. `probe 516.59`
+    Probe {
+        #[clap(value_parser)]
+        version: String,
+        /// Print JSON instead of a table
+        #[clap(long, value_parser, default_value = "false")]
+        json: bool,
+        /// Requests in flight at once
+        #[clap(long, value_parser, default_value = "8")]
+        concurrency: usize,
+        /// Minimum time between two requests, in milliseconds
+        #[clap(long, value_parser, default_value = "100")]
+        delay: u64,
+    },
+    /// Print the latest driver for the detected GPU supporting a CUDA release, e.g. `cuda 12.2 studio`
+    Cuda {
+        #[clap(value_parser)]
+        version: String,
+        #[clap(value_enum, default_value = "game-ready")]
+        channel: DriverChannels,
+        #[clap(flatten)]
+        detect: DetectOptions,
+    },
+    /// Print the link to the latest driver of a channel for the detected GPU
+    Latest {
+        #[clap(value_enum, default_value = "game-ready")]
+        channel: DriverChannels,
+        /// Look for hotfixes after this version instead of the latest Game Ready driver
+        #[clap(long, value_parser)]
+        after: Option<String>,
+        #[clap(flatten)]
+        detect: DetectOptions,
+    },
+    /// Print the links of a version for the detected GPU, e.g. `link 474.44`
+    Link {
+        #[clap(value_parser)]
+        version: String,
+        #[clap(value_enum, default_value = "game-ready")]
+        channel: DriverChannels,
+        #[clap(flatten)]
+        detect: DetectOptions,
+    },
+    /// Download or copy a driver and extract it for `strip` and `setup`
+    Extract {
+        /// Direct link or local installer, e.g. a package on a network share.
+        /// Defaults to the latest driver of the channel for the detected GPU.
+        #[clap(long, value_parser)]
+        source: Option<String>,
+        #[clap(long, value_enum, default_value = "game-ready")]
+        channel: DriverChannels,
+        #[clap(flatten)]
+        detect: DetectOptions,
+    },
+    /// Check whether a driver officially supports a GPU, e.g. `supports <details page> "GeForce RTX 3070"`
+    Supports {
+        /// Details page of the driver, e.g. https://www.nvidia.com/Download/driverResults.aspx/191961/en-us
+        #[clap(value_parser)]
+        page: String,
+        /// Product name from the lookup list (see `products`), defaults to the detected GPU
+        #[clap(value_parser)]
+        product: Option<String>,
+        /// List every supported product instead
+        #[clap(long, value_parser, default_value = "false")]
+        list: bool,
+        #[clap(flatten)]
+        detect: DetectOptions,
+    },
+    /// List NVIDIA's products by type and series, e.g. `products --series 123`
+    Products {
+        /// Only list the products of this series (the psid)
+        #[clap(long, value_parser)]
+        series: Option<u16>,
+        /// Only list products whose name contains this, with their series
+        #[clap(long, value_parser)]
+        search: Option<String>,
+    },
+    /// Run the setup of an extracted, and possibly stripped, driver
+    Setup {
+        /// Extracted driver, defaults to the last one extracted by NVIX
+        #[clap(long, value_parser)]
+        dir: Option<PathBuf>,
+    },
+    /// List the components that can be stripped, including the user's own catalog
+    Components {
+        /// Only list components applying to this driver version
+        #[clap(long, value_parser)]
+        version: Option<String>,
+        /// Catalog to merge instead of the one in the config directory
+        #[clap(long, value_parser)]
+        catalog: Option<PathBuf>,
+    },
+    /// List the components of an extracted driver as described by its manifests
+    Package {
+        /// Extracted driver, defaults to the last one extracted by NVIX
+        #[clap(value_parser)]
+        dir: Option<PathBuf>,
+    },
+    /// Show what removing components would break or pull in and how setup.cfg would change, e.g. `check gfe module-tracker`
+    Check {
+        /// Catalog ids (see `components`) or package names (see `package`)
+        #[clap(value_parser, required = true)]
+        components: Vec<String>,
+        /// Extracted driver, defaults to the last one extracted by NVIX
+        #[clap(long, value_parser)]
+        dir: Option<PathBuf>,
+        /// Catalog with extra components to merge, can be given more than once
+        #[clap(long = "preset", value_parser)]
+        presets: Vec<PathBuf>,
+    },
+    /// Remove components from an extracted driver, e.g. `strip telemetry gfe module-tracker --dry-run`
+    Strip {
+        /// Catalog ids (see `components`) or package names (see `package`), a profile is picked interactively if none are given
+        #[clap(value_parser)]
+        components: Vec<String>,
+        /// Remove what a profile removes (see `profiles`), plus the components given
+        #[clap(long, value_parser)]
+        profile: Option<String>,
+        /// Extracted driver, defaults to the last one extracted by NVIX
+        #[clap(long, value_parser)]
+        dir: Option<PathBuf>,
+        /// Remove them even if kept components depend on them
+        #[clap(long, value_parser, default_value = "false")]
+        force: bool,
+        /// Only show what would be removed and how setup.cfg would change
+        #[clap(long, value_parser, default_value = "false")]
+        dry_run: bool,
+        /// Delete them instead of moving them to the quarantine
+        #[clap(long, value_parser, default_value = "false")]
+        permanent: bool,
+        /// Remove translations to every language except English and those given with `--keep-locale`
+        #[clap(long, value_parser, default_value = "false")]
+        prune_locales: bool,
+        /// Language to keep when pruning, e.g. `--keep-locale de-DE`, implies `--prune-locales`
+        #[clap(long = "keep-locale", value_parser)]
+        keep_locales: Vec<String>,
+        /// Catalog with extra components to merge, can be given more than once
+        #[clap(long = "preset", value_parser)]
+        presets: Vec<PathBuf>,
+    },
+    /// List the strip profiles and what they remove
+    Profiles {
+        /// Only list components applying to this driver version
+        #[clap(long, value_parser)]
+        version: Option<String>,
+    },
+    /// Put stripped components back from the quarantine, all of them if none are given
+    Restore {
+        #[clap(value_parser)]
+        components: Vec<String>,
+        /// Extracted driver, defaults to the last one extracted by NVIX
+        #[clap(long, value_parser)]
+        dir: Option<PathBuf>,
+        /// Only list what's in quarantine
+        #[clap(long, value_parser, default_value = "false")]
+        list: bool,
+    },
+}
+
+/// How to detect the GPU for commands that look up drivers
+#[derive(Args, Debug)]
+pub struct DetectOptions {
+    /// Identify the GPU with a local copy of NVIDIA's supported-gpus.json instead of pci.ids
+    #[clap(long = "device-db", value_parser)]
+    device_db: Option<PathBuf>,
+    /// Pick the GPU from NVIDIA's product list instead of detecting it
+    #[clap(long, action)]
+    pick: bool,
+    /// Use the desktop or notebook drivers instead of guessing from the GPU
+    #[clap(long, value_enum)]
+    platform: Option<DriverPlatform>,
+    /// Operating system to look up drivers for, e.g. "Windows 11", defaults to Windows 10 64-bit
+    #[clap(long, value_parser)]
+    os: Option<String>,
+    /// Language of the driver page and package, e.g. "Japanese", defaults to English (US)
+    #[clap(long, value_parser)]
+    language: Option<String>,
+}
+
+impl DetectOptions {
+    fn database(&self) -> DeviceDatabase {
+        match &self.device_db {
+            Some(path) => DeviceDatabase::SupportedGpus(path.clone()),
+            None => DeviceDatabase::default(),
+        }
+    }
+}
+
+/// A lookup for the detected GPU
+async fn detected_query(
+    channel: DriverChannels,
+    detect: &DetectOptions,
+) -> Result<DriverQuery, Box<dyn Error>> {
+    let (gpu, product) = match detect.pick {
+        true => {
+            let product = crate::tui::gpu_selector()
+                .await?
+                .ok_or("No GPU was picked")?;
+            let gpu = DetectedGpu {
+                device_id: String::new(),
+                name: product.name.clone(),
+                chip: None,
+                legacy_branch: None,
+            };
+            (gpu, product)
+        }
+        false => {
+            let (gpu, product, confidence) = nvapi::detect_product(&detect.database()).await?;
+            if confidence < Confidence::High {
+                eprintln!(
+                    "Warning: not sure the detected GPU ({}, device {}) is \"{}\"",
+                    gpu.name,
+                    gpu.device_id,
+                    product.display_name()
+                );
+            }
+            (gpu, product)
+        }
+    };
+    let driver = Driver {
+        channel,
+        ..Driver::detected(String::new(), &gpu, Some(&product), detect.platform)
+    };
+    Ok(DriverQuery {
+        support: Support::of(&gpu),
+        gpu: product,
+        driver,
+        target: LookupTarget::by_name(detect.os.as_deref(), detect.language.as_deref()).await?,
+    })
+}
+
+/// Sources that failed before another one found the driver, e.g. the JSON API changing
+fn warn_failures(fallback: &Fallback) {
+    for failure in fallback.failures() {
+        eprintln!("Warning: {failure}");
+    }
+}
+
+/// Latest release on NVIDIA's sources for a query
+async fn nvidia_release(query: DriverQuery) -> Result<DriverRelease, Box<dyn Error>> {
+    let fallback = Fallback::nvidia(query);
+    let release = fallback.release().await?;
+    warn_failures(&fallback);
+    Ok(release)
+}
+
+/// Latest Game Ready or Studio driver for the detected GPU
+async fn latest_release(
+    channel: DriverChannels,
+    detect: &DetectOptions,
+) -> Result<DriverRelease, Box<dyn Error>> {
+    nvidia_release(detected_query(channel, detect).await?).await
+}
+
+pub async fn run(command: Command) -> Result<(), Box<dyn Error>> {
+    match command {
+        Command::Scan {
+            major,
+            concurrency,
+            delay,
+        } => {
+            let options = ScanOptions {
+                concurrency,
+                delay: Duration::from_millis(delay),
+                ..Default::default()
+            };
+            println!("Scanning {major}.00 to {major}.99, please wait...");
+            let hits = scan::scan(major, &options).await;
+            if hits.is_empty() {
+                return Err(format!("No builds found for R{major}").into());
+            }
+            for hit in hits {
+                println!("{hit}");
+            }
+        }
+        Command::Probe {
+            version,
+            json,
+            concurrency,
+            delay,
+        } => {
+            let version: DriverVersion = version.parse()?;
+            let entries =
+                scan::probe_matrix(version, concurrency, Duration::from_millis(delay)).await;
+            match json {
+                true => println!("{}", serde_json::to_string_pretty(&entries)?),
+                false => print_matrix(&entries),
+            }
+        }
+        Command::Cuda {
+            version,
+            channel,
+            detect,
+        } => {
+            let cuda: CudaVersion = version.parse()?;
+            if channel.into_api().is_none() {
+                return Err(
+                    "Only Game Ready and Studio drivers can be looked up by CUDA release".into(),
+                );
+            }
+            let service = AjaxDriverService::new(detected_query(channel, &detect).await?);
+            let release = cuda::latest_supporting(&service, cuda).await?;
+            let max = cuda::max_cuda(release.version).unwrap_or(cuda);
+            println!(
+                "{} (supports up to CUDA {max}): {}",
+                release.version, release.download_url
+            );
+        }
+        Command::Latest {
+            channel,
+            after,
+            detect,
+        } => {
+            // Vulkan betas and hotfixes aren't looked up for a product, so check the branch here
+            let link = match channel {
+                DriverChannels::VulkanBeta => {
+                    let support = detected_query(channel, &detect).await?.support;
+                    let (version, link) = channels::latest_vulkan_beta().await?;
+                    support.check(&version)?;
+                    link
+                }
+                DriverChannels::Hotfix => {
+                    let query = detected_query(DriverChannels::GameReady, &detect).await?;
+                    let after: DriverVersion = match after {
+                        Some(after) => after.parse()?,
+                        None => nvidia_release(query.clone()).await?.version,
+                    };
+                    let hit = channels::latest_hotfix(after).await?;
+                    query.support.check(&hit.version)?;
+                    hit.link
+                }
+                channel => latest_release(channel, &detect).await?.download_url,
+            };
+            println!("{link}");
+        }
+        Command::Link {
+            version,
+            channel,
+            detect,
+        } => {
+            let query = detected_query(channel, &detect).await?;
+            let driver = Driver {
+                version,
+                ..query.driver
+            };
+            for link in nvapi::new_link_supported(&driver, &query.support).await? {
+                println!("{link}");
+            }
+        }
+        Command::Extract {
+            source,
+            channel,
+            detect,
+        } => {
+            let (installer, name) = match source {
+                Some(source) => {
+                    let source = source::from_arg(&source);
+                    (source.acquire().await?, source.name())
+                }
+                None if channel.into_api().is_none() => {
+                    return Err(format!(
+                        "{channel:?} drivers can't be looked up, pass the link from `latest` with --source"
+                    )
+                    .into())
+                }
+                None => {
+                    let fallback = Fallback::nvidia(detected_query(channel, &detect).await?);
+                    let installer = fallback.acquire().await?;
+                    warn_failures(&fallback);
+                    (installer, "NVIDIA website")
+                }
+            };
+            nvapi::extract(&installer).await?;
+            println!(
+                "Extracted the driver from the {name} to {}",
+                crate::TMP_EXTRACT_DIR.display()
+            );
+        }
+        Command::Supports {
+            page,
+            product,
+            list,
+            detect,
+        } => {
+            let release = nvapi::parse_driver_page(page).await?;
+            if release.supported_products.is_empty() {
+                return Err("The page doesn't list any supported products".into());
+            }
+            let products = match (list, product) {
+                (true, _) => nvapi::xml::get_gpu_list().await?,
+                (false, Some(name)) => nvapi::xml::get_gpu_list()
+                    .await?
+                    .into_iter()
+                    .filter(|entry| entry.name == name)
+                    .collect(),
+                (false, None) => vec![detected_query(release.channel, &detect).await?.gpu],
+            };
+            if products.is_empty() {
+                return Err("No such product, see `products --search`".into());
+            }
+            let supported = release.supported_entries(&products);
+            for product in &supported {
+                println!("{}", product.display_name());
+            }
+            if !list && supported.is_empty() {
+                return Err(format!(
+                    "Driver {} doesn't support {}",
+                    release.version, products[0].name
+                )
+                .into());
+            }
+        }
+        Command::Products { series, search } => {
+            let tree = nvapi::xml::get_product_tree().await?;
+            if let Some(search) = search {
+                for product in tree.products().filter(|p| p.name.contains(&search)) {
+                    println!(
+                        "{:>5} {:>5}  {}",
+                        product.series,
+                        product.id,
+                        product.display_name()
+                    );
+                }
+                return Ok(());
+            }
+            let print_series = |series: &nvapi::xml::ProductSeries| {
+                let notebook = match series.is_notebook() {
+                    true => " (notebook drivers)",
+                    false => "",
+                };
+                println!("{:>5} {}{notebook}", series.id, series.name);
+                for product in &series.products {
+                    println!("{:>5} {:>5}  {}", "", product.id, product.name);
+                }
+            };
+            match series {
+                Some(psid) => print_series(
+                    tree.find_series(psid)
+                        .ok_or_else(|| format!("No series with the id {psid}"))?,
+                ),
+                None => {
+                    for product_type in &tree.types {
+                        println!("{} {}", product_type.id, product_type.name);
+                        product_type.series.iter().for_each(print_series);
+                    }
+                    if !tree.unlisted.is_empty() {
+                        println!("Unlisted");
+                        for product in &tree.unlisted {
+                            println!("{:>5} {:>5}  {}", product.series, product.id, product.name);
+                        }
+                    }
+                }
+            }
+        }
+        Command::Setup { dir } => {
+            let root = dir.unwrap_or_else(|| crate::TMP_EXTRACT_DIR.to_path_buf());
+            setup::setup(&root).await?;
+        }
+        Command::Components { version, catalog } => {
+            let version: Option<DriverVersion> = version.map(|v| v.parse()).transpose()?;
+            let catalog = Catalog::load_default(catalog.as_deref())?;
+            for component in &catalog.components {
+                if !component.applies_to(version) {
+                    continue;
+                }
+                let safety = match component.safe_to_remove {
+                    true => "",
+                    false => " (not safe to remove)",
+                };
+                println!("{:<12} {}{safety}", component.id, component.name);
+                if !component.description.is_empty() {
+                    println!("{:<12} {}", "", component.description);
+                }
+            }
+        }
+        Command::Package { dir } => {
+            let root = dir.unwrap_or_else(|| crate::TMP_EXTRACT_DIR.to_path_buf());
+            let package = Package::load(&root)?;
+            println!(
+                "{} {}",
+                package.title.as_deref().unwrap_or("Driver package"),
+                package.version.as_deref().unwrap_or("")
+            );
+            for component in &package.components {
+                let hidden = match component.hidden {
+                    true => " (hidden)",
+                    false => "",
+                };
+                println!(
+                    "{:<36} {:<16} {}{hidden}",
+                    component.id,
+                    component.version.as_deref().unwrap_or("?"),
+                    component.name
+                );
+                let requires: Vec<&str> = component.requires().collect();
+                if !requires.is_empty() {
+                    println!("{:<36} requires {}", "", requires.join(", "));
+                }
+            }
+        }
+        Command::Check {
+            components,
+            dir,
+            presets,
+        } => {
+            let root = dir.unwrap_or_else(|| crate::TMP_EXTRACT_DIR.to_path_buf());
+            let package = Package::load(&root)?;
+            let catalog = Catalog::load_with(&presets)?;
+            let version = package.version.as_deref().and_then(|v| v.parse().ok());
+            for warning in catalog.validate(&package.root, version, &components)? {
+                println!("Warning: {warning}");
+            }
+            setup::selected_components(&package, &catalog, &components)?;
+            let graph = DependencyGraph::build(&package, &catalog);
+            let check = graph.check(components.iter().map(String::as_str));
+            print!("{check}");
+            if check.is_safe() {
+                println!("Safe to remove");
+            }
+            let rules = PatchRules::for_removed(&package, &check.removed);
+            for patch in patch::patch_package(&package, &rules)? {
+                print!("{}", patch.diff());
+            }
+        }
+        Command::Strip {
+            mut components,
+            profile,
+            dir,
+            force,
+            dry_run,
+            permanent,
+            prune_locales,
+            keep_locales,
+            presets,
+        } => {
+            let root = dir.unwrap_or_else(|| crate::TMP_EXTRACT_DIR.to_path_buf());
+            let package = Package::load(&root)?;
+            let catalog = Catalog::load_with(&presets)?;
+            let version = package.version.as_deref().and_then(|v| v.parse().ok());
+            let pruning = prune_locales || !keep_locales.is_empty();
+            // Only pruning languages doesn't need components
+            let profile = match (profile, components.is_empty() && !pruning) {
+                (None, true) => match crate::tui::profile_selector(&catalog)? {
+                    Some(profile) => Some(profile.id),
+                    None => return Err("No profile selected, nothing to strip".into()),
+                },
+                (profile, _) => profile,
+            };
+            if let Some(profile) = profile {
+                let profile = catalog
+                    .profile(&profile)
+                    .ok_or_else(|| format!("Unknown profile \"{profile}\""))?;
+                for id in profile.selection(&catalog, version) {
+                    if !components.contains(&id) {
+                        components.push(id);
+                    }
+                }
+            }
+            println!("{}", catalog.summary(&components, version));
+            let options = setup::StripOptions {
+                force,
+                dry_run,
+                permanent,
+                keep_locales: match pruning {
+                    true => Some(keep_locales),
+                    false => None,
+                },
+            };
+            let report = setup::strip(&package, &catalog, &components, options).await?;
+            if dry_run {
+                for patch in &report.patches {
+                    print!("{}", patch.diff());
+                }
+            }
+            print!("{report}");
+            if report.has_failures() {
+                return Err("Some components could not be removed".into());
+            }
+        }
+        Command::Profiles { version } => {
+            let version: Option<DriverVersion> = version.map(|v| v.parse()).transpose()?;
+            let catalog = Catalog::load_default(None)?;
+            for profile in &catalog.profiles {
+                println!(
+                    "{:<12} {}: {}",
+                    profile.id, profile.name, profile.description
+                );
+                let summary = catalog.summary(&profile.selection(&catalog, version), version);
+                for line in summary.lines() {
+                    println!("{:<12} {line}", "");
+                }
+            }
+        }
+        Command::Restore {
+            components,
+            dir,
+            list,
+        } => {
+            let root = dir.unwrap_or_else(|| crate::TMP_EXTRACT_DIR.to_path_buf());
+            let mut quarantine = Quarantine::open(&root)?;
+            if list {
+                for entry in quarantine.entries() {
+                    println!(
+                        "{:<16} {:<24} {:>10}",
+                        entry.id,
+                        entry.name,
+                        setup::format_size(entry.bytes)
+                    );
+                }
+                return Ok(());
+            }
+            for id in quarantine.restore(&components)? {
+                println!("Restored {id}");
+            }
+        }
+    }
+    Ok(())
+}
+
+fn print_matrix(entries: &[MatrixEntry]) {
+    println!(
+        "{:<12} {:<10} {:<8} {:<8} {:>10}  Link",
+        "Channel", "Platform", "Edition", "Windows", "Size"
+    );
+    for entry in entries.iter().filter(|entry| entry.exists) {
+        let size = entry.size.map_or("?".to_string(), setup::format_size);
+        println!(
+            "{:<12} {:<10} {:<8} {:<8} {:>10}  {}",
+            format!("{:?}", entry.channel),
+            format!("{:?}", entry.platform),
+            format!("{:?}", entry.edition),
+            format!("{:?}", entry.windows),
+            size,
+            entry.link
+        );
+    }
+    let found = entries.iter().filter(|entry| entry.exists).count();
+    println!("{found} of {} combinations exist", entries.len());
+}
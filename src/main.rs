@@ -1,4 +1,4 @@
-use std::{io::Result, io::Write, path::PathBuf};
+use std::{io::Result, io::Write, path::PathBuf, rc::Rc};
 
 use clap::Parser;
 use nvapi::{xml::get_gpu_list, detect_gpu};
@@ -6,10 +6,13 @@ use once_cell::sync::Lazy;
 use slint::{SharedString, ModelRc};
 
 use crate::nvapi::{xml::XmlGpuEntry, DriverChannels, DriverEdition, DriverPlatform};
+use crate::setup::{catalog::Catalog, package::Package};
+mod cli;
 mod nvapi;
 mod setup;
 #[cfg(test)]
 mod tests;
+mod tui;
 mod ui;
 
 static TMP_FILE: Lazy<PathBuf> = Lazy::new(|| {
@@ -35,6 +38,9 @@ slint::include_modules!();
 struct Args {
     #[clap(long, value_parser, default_value = "false")]
     verbose: bool,
+    /// Run a single operation instead of the UI
+    #[clap(subcommand)]
+    command: Option<cli::Command>,
 }
 
 #[tokio::main]
@@ -49,17 +55,42 @@ struct Args {
 async fn main() -> Result<()> {
     let args = Args::parse();
 
+    if let Some(command) = args.command {
+        if let Err(e) = cli::run(command).await {
+            eprintln!("Error: {e}");
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
     let orig: Vec<XmlGpuEntry> = get_gpu_list().await.unwrap();
     let list: slint::ModelRc<SharedString> = xml_vec_to_slint_vec(&orig.clone(), None);
 
+    let catalog = Rc::new(Catalog::load_default(None).unwrap_or_else(|e| {
+        eprintln!("Error: {e}, using the built-in components");
+        Catalog::builtin()
+    }));
+    let strip_catalog = catalog.clone();
+    let profiles: Vec<SharedString> = catalog.profiles.iter().map(|p| SharedString::from(p.name.as_str())).collect();
+
     let ui = AppWindow::new();
     let ui_weak = ui.as_weak();
     let ui_weak_pages = ui_weak.clone();
+    let ui_weak_profiles = ui_weak.clone();
     ui.set_list(list);
+    ui.set_profiles(ModelRc::new(slint::VecModel::from(profiles)));
     ui.on_search(move |search| {
         let ui = ui_weak.upgrade().unwrap();
         ui.set_list(xml_vec_to_slint_vec(&orig, Some(search.clone().as_str())));
     });
+    ui.on_profile_selected(move |name| {
+        let ui = ui_weak_profiles.upgrade().unwrap();
+        // Show what the profile removes before anything is stripped
+        if let Some(profile) = catalog.profiles.iter().find(|p| p.name == name.as_str()) {
+            let summary = format!("{}\n\n{}", profile.description, catalog.summary(&profile.selection(&catalog, None), None));
+            ui.set_profile_summary(SharedString::from(summary));
+        }
+    });
     ui.on_page_move(move |page| {
         let ui = ui_weak_pages.upgrade().unwrap();
         println!("On page: {}", page);
@@ -67,6 +98,14 @@ async fn main() -> Result<()> {
             1 => {
                 println!("{}", ui.get_selection());
             }
+            2 => {
+                // Strip the last extracted driver with the selected profile
+                let report = match strip_profile(&strip_catalog, ui.get_profile().as_str()) {
+                    Ok(report) => report,
+                    Err(e) => format!("Error: {e}"),
+                };
+                ui.set_strip_report(SharedString::from(report));
+            }
             _ => {} 
         }
     });
@@ -74,14 +113,27 @@ async fn main() -> Result<()> {
     Ok(())
 }
 
+/// Strips the driver in `TMP_EXTRACT_DIR` with a profile, by name, and returns the report
+fn strip_profile(catalog: &Catalog, name: &str) -> std::result::Result<String, Box<dyn std::error::Error>> {
+    let profile = catalog.profiles.iter().find(|p| p.name == name).ok_or("No profile selected")?;
+    let package = Package::load(&TMP_EXTRACT_DIR)?;
+    let version = package.version.as_deref().and_then(|v| v.parse().ok());
+    let selection = profile.selection(catalog, version);
+    // Callbacks run on the UI thread inside the runtime, strip doesn't wait on anything but the disk
+    let report = tokio::task::block_in_place(|| {
+        tokio::runtime::Handle::current().block_on(setup::strip(&package, catalog, &selection, setup::StripOptions::default()))
+    })?;
+    Ok(format!("{}\n\n{report}", catalog.summary(&selection, version)))
+}
+
 /// I fucking love strong types!
 fn xml_vec_to_slint_vec(xml: &Vec<XmlGpuEntry>, filter: Option<&str>) -> ModelRc<SharedString> {
         let list: Vec<slint::SharedString> = match filter {
             Some(filter) => {
-                xml.iter().filter(|x| x.name.contains(&*filter)).map(|x| slint::SharedString::from(&x.name)).collect()
+                xml.iter().map(|x| x.display_name()).filter(|x| x.contains(&*filter)).map(slint::SharedString::from).collect()
             }
             None => {
-                xml.iter().map(|x| slint::SharedString::from(&x.name)).collect()
+                xml.iter().map(|x| slint::SharedString::from(x.display_name())).collect()
             }
         };
         let list: slint::VecModel<slint::SharedString> = list.into();
@@ -2,7 +2,12 @@
 //! This module contains actions related to th&e NVIDIA API. Not to be confused with NVIDIA's driver api.
 //! Reference: <https://github.com/fyr77/EnvyUpdate/wiki/Nvidia-API>
 
-use std::{error::Error, fs::File, io::Cursor};
+use std::{
+    error::Error,
+    fs::File,
+    io::Cursor,
+    path::{Path, PathBuf},
+};
 
 const BASE_LINK: &str = "https://international.download.nvidia.com";
 const PCI_IDS: &str = "https://raw.githubusercontent.com/pciutils/pciids/master/pci.ids";
@@ -10,8 +15,10 @@ const SEVENZIP_LINK: &str = "https://www.7-zip.org/a/7zr.exe"; // I can't have a
 
 static REGEX_VENDOR: Lazy<Regex> = Lazy::new(|| Regex::new("^([0-9a-f]{4})  (.*)$").unwrap());
 static REGEX_DEVICE: Lazy<Regex> = Lazy::new(|| Regex::new("^\t([0-9a-f]{4})  (.*)$").unwrap());
+static REGEX_VERSION: Lazy<Regex> = Lazy::new(|| Regex::new("[0-9]{3}\\.[0-9]{2}").unwrap());
 //static REGEX_SUBDEVICE: Lazy<Regex> = Lazy::new(|| Regex::new("^\t\t([0-9a-f]{4}) (.*)$").unwrap());
 
+#[derive(Debug, Clone)]
 pub struct Driver {
     pub version: String,
     pub channel: DriverChannels,
@@ -19,11 +26,90 @@ pub struct Driver {
     pub edition: DriverEdition,
 }
 
-#[derive(Default, PartialEq)]
+impl Driver {
+    /// A driver for a detected GPU, with the platform inferred unless `platform` overrides it
+    pub fn detected(
+        version: String,
+        gpu: &DetectedGpu,
+        product: Option<&XmlGpuEntry>,
+        platform: Option<DriverPlatform>,
+    ) -> Driver {
+        Driver {
+            version,
+            channel: DriverChannels::default(),
+            platform: platform.unwrap_or_else(|| DriverPlatform::infer(gpu, product)),
+            edition: DriverEdition::default(),
+        }
+    }
+
+    pub fn parsed_version(&self) -> Result<DriverVersion, Box<dyn Error>> {
+        self.version.parse()
+    }
+
+    /// Rejects combinations that can never exist, e.g. a Standard package of a DCH only version, see [`rules`]
+    pub fn validate(&self) -> Result<(), Box<dyn Error>> {
+        rules::check(self, self.parsed_version()?)?;
+        Ok(())
+    }
+}
+
+/// A driver version such as "516.59", ordered by branch then release
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, serde::Deserialize)]
+#[serde(try_from = "String")]
+pub struct DriverVersion {
+    pub major: u16, // e.g. 516
+    pub minor: u16, // e.g. 59
+}
+
+impl DriverVersion {
+    pub const fn new(major: u16, minor: u16) -> DriverVersion {
+        DriverVersion { major, minor }
+    }
+
+    /// Finds the version in an installer's file name, e.g. "516.59-desktop-win10-win11-64bit-international-dch-whql.exe"
+    pub fn from_file_name(name: &str) -> Option<DriverVersion> {
+        REGEX_VERSION
+            .find(name)
+            .and_then(|version| version.as_str().parse().ok())
+    }
+}
+
+impl std::str::FromStr for DriverVersion {
+    type Err = Box<dyn Error>;
+
+    fn from_str(version: &str) -> Result<Self, Self::Err> {
+        let (major, minor) = version
+            .trim()
+            .split_once('.')
+            .ok_or_else(|| format!("Invalid driver version \"{version}\""))?;
+        Ok(DriverVersion {
+            major: major.parse()?,
+            minor: minor.parse()?,
+        })
+    }
+}
+
+impl TryFrom<String> for DriverVersion {
+    type Error = String;
+
+    fn try_from(version: String) -> Result<Self, Self::Error> {
+        version.parse().map_err(|e: Box<dyn Error>| e.to_string())
+    }
+}
+
+impl std::fmt::Display for DriverVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}.{:02}", self.major, self.minor)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, clap::ValueEnum, serde::Serialize)]
 pub enum DriverChannels {
     #[default]
     GameReady,
     Studio,
+    Hotfix,     // not in the lookup API, see `channels::latest_hotfix`
+    VulkanBeta, // hosted on developer.nvidia.com, see `channels::latest_vulkan_beta`
 }
 
 impl std::fmt::Display for DriverChannels {
@@ -31,20 +117,34 @@ impl std::fmt::Display for DriverChannels {
         match self {
             DriverChannels::GameReady => write!(f, ""),
             DriverChannels::Studio => write!(f, "-nsd"),
+            DriverChannels::Hotfix => write!(f, ".hf"),
+            DriverChannels::VulkanBeta => write!(f, "-vulkan-beta"), // the link templates don't need it
         }
     }
 }
 
 impl DriverChannels {
-    pub fn into_api(self) -> u8 {
+    pub fn iter() -> std::slice::Iter<'static, Self> {
+        [
+            Self::GameReady,
+            Self::Studio,
+            Self::Hotfix,
+            Self::VulkanBeta,
+        ]
+        .iter()
+    }
+
+    /// The `whql` value of the lookup API, which only knows Game Ready and Studio drivers
+    pub fn into_api(self) -> Option<u8> {
         match self {
-            DriverChannels::GameReady => 1,
-            DriverChannels::Studio => 4,
+            DriverChannels::GameReady => Some(1),
+            DriverChannels::Studio => Some(4),
+            DriverChannels::Hotfix | DriverChannels::VulkanBeta => None,
         }
     }
 }
 
-#[derive(Default, PartialEq)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, clap::ValueEnum, serde::Serialize)]
 pub enum DriverPlatform {
     #[default]
     Desktop,
@@ -60,7 +160,43 @@ impl std::fmt::Display for DriverPlatform {
     }
 }
 
-#[derive(Default, PartialEq)]
+impl DriverPlatform {
+    pub fn iter() -> std::slice::Iter<'static, Self> {
+        [Self::Desktop, Self::Notebook].iter()
+    }
+
+    /// Guesses whether a detected GPU is a mobile part, notebooks need their own driver package.
+    /// `product` is the matching lookup entry, if known (see [`resolve::resolve_gpu`]).
+    pub fn infer(gpu: &DetectedGpu, product: Option<&XmlGpuEntry>) -> DriverPlatform {
+        // Mobile chips have an "M" suffix in pci.ids (e.g. "GA104M"), or are "GN" parts (e.g. "GN20-E5")
+        let mobile_chip = gpu
+            .chip
+            .as_deref()
+            .is_some_and(|chip| chip.ends_with('M') || chip.starts_with("GN"));
+        // "Laptop GPU", "Mobile", "Max-Q", or a notebook only model like the MX series
+        let mobile_name = |name: &str| {
+            let name = resolve::normalise(name);
+            name.mobile || name.models.iter().any(|model| model.starts_with("mx"))
+        };
+
+        // Products in a notebook series, e.g. "GeForce RTX 30 Series (Notebooks)"
+        let mobile_series = product
+            .and_then(|product| product.series_name.as_deref())
+            .is_some_and(xml::is_notebook_series);
+
+        if mobile_chip
+            || mobile_series
+            || mobile_name(&gpu.name)
+            || product.is_some_and(|product| mobile_name(&product.name))
+        {
+            DriverPlatform::Notebook
+        } else {
+            DriverPlatform::Desktop
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, serde::Serialize)]
 pub enum DriverEdition {
     #[default]
     DCH, // Desktop Channel, UWP
@@ -77,6 +213,10 @@ impl std::fmt::Display for DriverEdition {
 }
 
 impl DriverEdition {
+    pub fn iter() -> std::slice::Iter<'static, Self> {
+        [Self::DCH, Self::STD].iter()
+    }
+
     pub fn into_api(self) -> u8 {
         match self {
             DriverEdition::DCH => 1,
@@ -85,7 +225,7 @@ impl DriverEdition {
     }
 }
 
-#[derive(Default, PartialEq)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, serde::Serialize)]
 pub enum DriverWindowsVersion {
     #[default]
     Win11, // Allows for both 10 and 11
@@ -107,25 +247,61 @@ impl DriverWindowsVersion {
     }
 }
 
+/// Every link a driver could be hosted at, one per Windows version naming.
+/// Combinations which can't exist (see [`rules`]) are left out.
+pub fn candidate_links(driver: &Driver) -> Vec<(DriverWindowsVersion, String)> {
+    let mut links = all_links(driver);
+    if let Ok(version) = driver.parsed_version() {
+        match rules::check(driver, version) {
+            Ok(()) => links.retain(|(winver, _)| rules::check_windows(driver, version, *winver).is_ok()),
+            Err(_) => links.clear(),
+        }
+    }
+    links
+}
+
+fn all_links(driver: &Driver) -> Vec<(DriverWindowsVersion, String)> {
+    let version: &str = &driver.version;
+    let platform: &str = &driver.platform.to_string();
+    let channel: &str = &driver.channel.to_string();
+    let edition: &str = &driver.edition.to_string();
+
+    match driver.channel {
+        // Construct link with values that always exist
+        DriverChannels::GameReady | DriverChannels::Studio => DriverWindowsVersion::iter().map(|winver| {
+            let link: String = format!("{BASE_LINK}/Windows/{version}/{version}-{platform}{winver}-64bit-international{channel}{edition}-whql.exe");
+            (*winver, link)
+        }).collect(),
+        // Hotfixes are DCH only, not WHQL and live in their own folder. Newer ones are a single desktop and notebook package.
+        DriverChannels::Hotfix if driver.edition == DriverEdition::DCH => {
+            let winver = DriverWindowsVersion::Win11;
+            [platform, "desktop-notebook"].iter().map(|platform| {
+                let link: String = format!("{BASE_LINK}/Windows/{version}hf/{version}-{platform}{winver}-64bit-international{edition}{channel}.exe");
+                (winver, link)
+            }).collect()
+        }
+        DriverChannels::Hotfix => Vec::new(),
+        // Vulkan betas are one package for every platform, named by the version without the dot, e.g. "vulkan-beta-53867-windows"
+        DriverChannels::VulkanBeta => {
+            let (base, version) = (channels::VULKAN_BETA_LINK, version.replace('.', ""));
+            vec![
+                (DriverWindowsVersion::Win11, format!("{base}/downloads/vulkan-beta-{version}-windows")),
+                (DriverWindowsVersion::Win11, format!("{base}/vulkan-beta-{version}-windows")),
+                (DriverWindowsVersion::Win10, format!("{base}/vulkan-beta-{version}-windows-10")),
+            ]
+        }
+    }
+}
+
 pub async fn new_link(driver: &Driver) -> Result<Vec<String>, Box<dyn Error>> {
+    // no need to ask NVIDIA about combinations that can't exist
+    driver.validate()?;
     let mut valid: Vec<String> = Vec::new();
-    {
-        let version: &str = &driver.version;
-        let platform: &str = &driver.platform.to_string();
-        let channel: &str = &driver.channel.to_string();
-        let edition: &str = &driver.edition.to_string();
 
-        // Construct link with values that always exist
-        let links: Vec<String> = DriverWindowsVersion::iter().map(|winver| {
-        let link: String = format!("{BASE_LINK}/Windows/{version}/{version}-{platform}{winver}-64bit-international{channel}{edition}-whql.exe");
-        link
-    }).collect();
-
-        // check links
-        for link in links {
-            if check_link(link.as_str()).await.is_ok() {
-                valid.push(link);
-            }
+    // check links
+    for (_, link) in candidate_links(driver) {
+        if check_link(link.as_str()).await.is_ok() {
+            valid.push(link);
         }
     }
 
@@ -135,6 +311,15 @@ pub async fn new_link(driver: &Driver) -> Result<Vec<String>, Box<dyn Error>> {
     Ok(valid)
 }
 
+/// Like [`new_link`], but refuses versions the GPU isn't supported by anymore
+pub async fn new_link_supported(
+    driver: &Driver,
+    support: &legacy::Support,
+) -> Result<Vec<String>, Box<dyn Error>> {
+    support.check(&driver.parsed_version()?)?;
+    new_link(driver).await
+}
+
 pub async fn check_link(link: &str) -> Result<(), Box<dyn Error>> {
     // Check if link exists
     let resp = reqwest::get(link).await?;
@@ -149,9 +334,89 @@ use once_cell::sync::Lazy;
 use regex::Regex;
 use tokio::join;
 
-use self::xml::XmlGpuEntry;
+use self::{release::DriverRelease, resolve::Confidence, xml::XmlGpuEntry};
+
+pub mod channels;
+pub mod cuda;
+pub mod devices;
+pub mod legacy;
+pub mod release;
+pub mod resolve;
+pub mod rules;
+pub mod scan;
+pub mod source;
+
+/// Where to look up the name of a detected device id
+#[derive(Default)]
+pub enum DeviceDatabase {
+    #[default]
+    PciIds, // fetched from `PCI_IDS`
+    SupportedGpus(PathBuf), // a local copy of NVIDIA's supported-gpus.json
+}
+
+/// PCI ids of a device, see [`PciIds::parse`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct PciIds {
+    pub device: String,                      // e.g. "2204"
+    pub subsystem: Option<(String, String)>, // vendor and device, e.g. ("10de", "147d")
+}
+
+impl PciIds {
+    /// Parses a hardware id such as "PCI\VEN_10DE&DEV_2204&SUBSYS_147D10DE&REV_A1", lower case like pci.ids
+    pub fn parse(hardware_id: &str) -> Option<PciIds> {
+        let hardware_id = hardware_id.to_ascii_lowercase();
+        let field = |name: &str| {
+            hardware_id
+                .split(['\\', '&'])
+                .find_map(|part| part.strip_prefix(name))
+                .map(str::to_string)
+        };
+        // SUBSYS_ is the subsystem device followed by its vendor
+        let subsystem = field("subsys_")
+            .filter(|subsys| subsys.len() == 8)
+            .map(|subsys| (subsys[4..].to_string(), subsys[..4].to_string()));
+        Some(PciIds {
+            device: field("dev_")?,
+            subsystem,
+        })
+    }
+}
+
+pub struct DetectedGpu {
+    pub device_id: String,             // e.g. "2204"
+    pub name: String,                  // e.g. "GeForce RTX 3090"
+    pub chip: Option<String>,          // e.g. "GA102", only known from pci.ids
+    pub legacy_branch: Option<String>, // e.g. "470.xx", only known from supported-gpus.json
+}
 
 pub async fn detect_gpu() -> Result<String, Box<dyn Error>> {
+    Ok(detect_device(&DeviceDatabase::default()).await?.name)
+}
+
+pub async fn detect_device(database: &DeviceDatabase) -> Result<DetectedGpu, Box<dyn Error>> {
+    match database {
+        DeviceDatabase::PciIds => detect_device_pci_ids().await,
+        DeviceDatabase::SupportedGpus(path) => {
+            let ids = crate::nvapi::get_gpu_ids().await?;
+            let gpus = devices::SupportedGpus::from_file(path)?;
+            let subsystem = ids
+                .subsystem
+                .as_ref()
+                .map(|(vendor, device)| (vendor.as_str(), device.as_str()));
+            let chip = gpus
+                .lookup(&ids.device, subsystem)
+                .ok_or("No matching device found")?;
+            Ok(DetectedGpu {
+                name: chip.name().to_string(),
+                device_id: ids.device,
+                chip: None,
+                legacy_branch: chip.legacybranch.clone(),
+            })
+        }
+    }
+}
+
+async fn detect_device_pci_ids() -> Result<DetectedGpu, Box<dyn Error>> {
     let mut vendor_id = String::new();
 
     // get pci device id list
@@ -187,7 +452,16 @@ pub async fn detect_gpu() -> Result<String, Box<dyn Error>> {
                         .next()
                         .unwrap()
                         .to_string();
-                    return Ok(name);
+                    // the chip is whatever comes before the brackets, e.g. "GA104M [GeForce RTX 3070 Mobile / Max-Q]"
+                    let chip = capture[2]
+                        .split_once('[')
+                        .map(|(chip, _)| chip.trim().to_string());
+                    return Ok(DetectedGpu {
+                        device_id,
+                        name,
+                        chip,
+                        legacy_branch: None,
+                    });
                 }
             }
             // SubDevices
@@ -203,16 +477,202 @@ pub async fn detect_gpu() -> Result<String, Box<dyn Error>> {
     Err("No matching device found".into())
 }
 
+/// Detects the GPU and finds its entry in NVIDIA's product list, ready for [`get_latest_driver_link`].
+pub async fn detect_product(
+    database: &DeviceDatabase,
+) -> Result<(DetectedGpu, XmlGpuEntry, Confidence), Box<dyn Error>> {
+    let (gpu, list) = join!(detect_device(database), xml::get_gpu_list());
+    let (gpu, list) = (gpu?, list?);
+    let platform = DriverPlatform::infer(&gpu, None);
+    let (product, confidence) = resolve::resolve_gpu(&gpu.name, Some(platform), &list)
+        .ok_or_else(|| format!("No NVIDIA product matches \"{}\"", gpu.name))?;
+    Ok((gpu, product, confidence))
+}
+
 pub mod xml {
     use std::error::Error;
 
     use serde::Deserialize;
+    use tokio::join;
+
+    const LOOKUP_LINK: &str = "https://www.nvidia.com/Download/API/lookupValueSearch.aspx";
 
-    #[derive(Clone, PartialEq)]
+    #[derive(Debug, Clone, PartialEq)]
     pub struct XmlGpuEntry {
-        pub name: String, // e.g. "GeForce RTX 3090 Ti"
-        pub series: u16,  // e.g. "120", the ParentID in the XML file
-        pub id: u16,      // e.g. "985"
+        pub name: String,                // e.g. "GeForce RTX 3090 Ti"
+        pub series: u16,                 // e.g. "120", the ParentID in the XML file
+        pub id: u16,                     // e.g. "985"
+        pub series_name: Option<String>, // e.g. "GeForce RTX 30 Series", if the series is known
+    }
+
+    impl XmlGpuEntry {
+        /// Name with the series, as the same product can be listed under several series
+        pub fn display_name(&self) -> String {
+            match &self.series_name {
+                Some(series) => format!("{} - {series}", self.name),
+                None => self.name.clone(),
+            }
+        }
+    }
+
+    /// An operating system (TypeID=4) or language (TypeID=5)
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct XmlLookupEntry {
+        pub name: String,         // e.g. "Windows 10 64-bit" or "Japanese"
+        pub id: u16,              // e.g. "57", the osid or lid
+        pub code: Option<String>, // e.g. "10.0"
+    }
+
+    impl From<&LookupValue> for XmlLookupEntry {
+        fn from(value: &LookupValue) -> Self {
+            XmlLookupEntry {
+                name: value.name.value.clone(),
+                id: value.value.value,
+                code: value.code.clone(),
+            }
+        }
+    }
+
+    /// Finds an entry by name, ignoring case. Falls back to the only entry containing every word of `name`,
+    /// so "windows 11" finds "Windows 11" and "linux 64" finds "Linux 64-bit", but "windows" is ambiguous.
+    pub fn find_entry<'a>(
+        list: &'a [XmlLookupEntry],
+        name: &str,
+    ) -> Result<&'a XmlLookupEntry, String> {
+        let lower = name.trim().to_lowercase();
+        if let Some(entry) = list.iter().find(|entry| entry.name.to_lowercase() == lower) {
+            return Ok(entry);
+        }
+        let matches: Vec<&XmlLookupEntry> = list
+            .iter()
+            .filter(|entry| {
+                let entry = entry.name.to_lowercase();
+                lower.split_whitespace().all(|word| entry.contains(word))
+            })
+            .collect();
+        match matches[..] {
+            [entry] => Ok(entry),
+            [] => Err(format!("\"{name}\" not found")),
+            _ => {
+                let names: Vec<&str> = matches.iter().map(|entry| entry.name.as_str()).collect();
+                Err(format!("\"{name}\" is ambiguous ({})", names.join(", ")))
+            }
+        }
+    }
+
+    /// Product types (TypeID=1) -> series (TypeID=2) -> products (TypeID=3)
+    #[derive(Debug, Clone, Default)]
+    pub struct ProductTree {
+        pub types: Vec<ProductType>,
+        pub unlisted: Vec<XmlGpuEntry>, // products whose series or type isn't in the lookup
+    }
+
+    #[derive(Debug, Clone)]
+    pub struct ProductType {
+        pub id: u16,      // e.g. "1"
+        pub name: String, // e.g. "GeForce"
+        pub series: Vec<ProductSeries>,
+    }
+
+    #[derive(Debug, Clone)]
+    pub struct ProductSeries {
+        pub id: u16,      // e.g. "123", the psid
+        pub name: String, // e.g. "GeForce RTX 30 Series (Notebooks)"
+        pub products: Vec<XmlGpuEntry>,
+    }
+
+    impl ProductSeries {
+        pub fn is_notebook(&self) -> bool {
+            is_notebook_series(&self.name)
+        }
+    }
+
+    /// Whether a series only has notebook GPUs, e.g. "GeForce RTX 30 Series (Notebooks)"
+    pub fn is_notebook_series(series: &str) -> bool {
+        series.contains("Notebook")
+    }
+
+    /// Products with the name of their series, `None` if the series isn't in `series`
+    pub fn gpu_entries(series: &[LookupValue], products: &[LookupValue]) -> Vec<XmlGpuEntry> {
+        products
+            .iter()
+            .map(|product| XmlGpuEntry {
+                name: product.name.value.clone(),
+                series: product.parentid,
+                id: product.value.value,
+                series_name: series
+                    .iter()
+                    .find(|series| series.value.value == product.parentid)
+                    .map(|series| series.name.value.clone()),
+            })
+            .collect()
+    }
+
+    impl ProductTree {
+        pub fn build(
+            types: &[LookupValue],
+            series: &[LookupValue],
+            products: &[LookupValue],
+        ) -> ProductTree {
+            let entries = gpu_entries(series, products);
+            let series: Vec<(u16, ProductSeries)> = series
+                .iter()
+                .map(|series| {
+                    let products = entries
+                        .iter()
+                        .filter(|product| product.series == series.value.value)
+                        .cloned()
+                        .collect();
+                    let entry = ProductSeries {
+                        id: series.value.value,
+                        name: series.name.value.clone(),
+                        products,
+                    };
+                    (series.parentid, entry)
+                })
+                .collect();
+
+            let types: Vec<ProductType> = types
+                .iter()
+                .map(|product_type| ProductType {
+                    id: product_type.value.value,
+                    name: product_type.name.value.clone(),
+                    series: series
+                        .iter()
+                        .filter(|(parent, _)| *parent == product_type.value.value)
+                        .map(|(_, series)| series.clone())
+                        .collect(),
+                })
+                .collect();
+            let listed = |psid: u16| {
+                types
+                    .iter()
+                    .flat_map(|product_type| &product_type.series)
+                    .any(|series| series.id == psid)
+            };
+            let unlisted = entries
+                .iter()
+                .filter(|product| !listed(product.series))
+                .cloned()
+                .collect();
+            ProductTree { types, unlisted }
+        }
+
+        pub fn series(&self) -> impl Iterator<Item = &ProductSeries> {
+            self.types
+                .iter()
+                .flat_map(|product_type| &product_type.series)
+        }
+
+        pub fn products(&self) -> impl Iterator<Item = &XmlGpuEntry> {
+            self.series()
+                .flat_map(|series| &series.products)
+                .chain(&self.unlisted)
+        }
+
+        pub fn find_series(&self, psid: u16) -> Option<&ProductSeries> {
+            self.series().find(|series| series.id == psid)
+        }
     }
 
     #[derive(Debug, Deserialize)]
@@ -229,8 +689,10 @@ pub mod xml {
 
     #[derive(Debug, Deserialize)]
     pub struct LookupValue {
-        #[serde(rename = "ParentID")]
+        #[serde(rename = "ParentID", default)]
         pub parentid: u16,
+        #[serde(rename = "Code", default)]
+        pub code: Option<String>, // only set for some tables, e.g. "10.0" for operating systems
         #[serde(rename = "Name")]
         pub name: Name,
         #[serde(rename = "Value")]
@@ -249,26 +711,43 @@ pub mod xml {
         pub value: u16,
     }
 
+    pub fn parse_lookup(xml: &str) -> Result<Vec<LookupValue>, Box<dyn Error>> {
+        let deserialized: LookupValueSearch = quick_xml::de::from_str(xml)?;
+        Ok(deserialized.lookupvalues.lookupvalue)
+    }
+
+    pub async fn lookup(type_id: u8) -> Result<Vec<LookupValue>, Box<dyn Error>> {
+        let xml = reqwest::get(format!("{LOOKUP_LINK}?TypeID={type_id}"));
+        parse_lookup(&xml.await?.text().await?)
+    }
+
+    pub async fn get_product_tree() -> Result<ProductTree, Box<dyn Error>> {
+        let (types, series, products) = join!(lookup(1), lookup(2), lookup(3));
+        Ok(ProductTree::build(&types?, &series?, &products?))
+    }
+
+    pub async fn get_os_list() -> Result<Vec<XmlLookupEntry>, Box<dyn Error>> {
+        Ok(lookup(4).await?.iter().map(XmlLookupEntry::from).collect())
+    }
+
+    pub async fn get_language_list() -> Result<Vec<XmlLookupEntry>, Box<dyn Error>> {
+        Ok(lookup(5).await?.iter().map(XmlLookupEntry::from).collect())
+    }
+
+    /// Every product, including the ones of unlisted series. Products are listed once per series they belong to,
+    /// e.g. "GeForce GTX 1060" is both in "GeForce 10 Series" and "GeForce 10 Series (Notebooks)".
     pub async fn get_gpu_list() -> Result<Vec<XmlGpuEntry>, Box<dyn Error>> {
-        let xml =
-            reqwest::get("https://www.nvidia.com/Download/API/lookupValueSearch.aspx?TypeID=3");
-        let deserialized: LookupValueSearch = quick_xml::de::from_str(&xml.await?.text().await?)?;
-
-        let mut gpu_entries: Vec<XmlGpuEntry> = Vec::new();
-        for lookupvalue in deserialized.lookupvalues.lookupvalue.iter() {
-            let gpu_entry = XmlGpuEntry {
-                name: lookupvalue.name.value.clone(),
-                series: lookupvalue.parentid,
-                id: lookupvalue.value.value,
-            };
-            gpu_entries.push(gpu_entry);
-        }
-        Ok(gpu_entries)
+        let (series, products) = join!(lookup(2), lookup(3));
+        Ok(gpu_entries(&series?, &products?))
     }
 }
 
-#[cfg(feature = "wmi")]
 pub async fn get_gpu_id() -> Result<String, Box<dyn Error>> {
+    Ok(get_gpu_ids().await?.device)
+}
+
+#[cfg(feature = "wmi")]
+pub async fn get_gpu_ids() -> Result<PciIds, Box<dyn Error>> {
     use serde::Deserialize;
 
     let com_connection: wmi::COMLibrary = wmi::COMLibrary::new()?;
@@ -290,10 +769,9 @@ pub async fn get_gpu_id() -> Result<String, Box<dyn Error>> {
         if driver.device_class == Some("DISPLAY".to_string())
             || driver.device_name == Some("3D Video Controller".to_string())
         {
-            if let Some(mut hwid) = driver.hardware_id {
-                hwid = hwid.split("DEV_").last().unwrap().to_string();
-                let parts: Vec<&str> = hwid.split('&').collect();
-                return Ok(parts[0].to_string().to_ascii_lowercase()); // WMI returns an uppercase hwid
+            // WMI returns an uppercase hwid, e.g. "PCI\VEN_10DE&DEV_2204&SUBSYS_147D10DE&REV_A1"
+            if let Some(ids) = driver.hardware_id.as_deref().and_then(PciIds::parse) {
+                return Ok(ids);
             }
         }
     }
@@ -302,9 +780,7 @@ pub async fn get_gpu_id() -> Result<String, Box<dyn Error>> {
 }
 
 #[cfg(feature = "reg")]
-pub async fn get_gpu_id() -> Result<String, Box<dyn Error>> {
-    let mut device_id = String::new();
-
+pub async fn get_gpu_ids() -> Result<PciIds, Box<dyn Error>> {
     // get device id from registry (if any)
     let hklm = winreg::RegKey::predef(winreg::enums::HKEY_LOCAL_MACHINE);
     let key = hklm.open_subkey(
@@ -316,39 +792,91 @@ pub async fn get_gpu_id() -> Result<String, Box<dyn Error>> {
         if subkey.len() == 4 {
             // subkeys for devices are 4 characters long, e.g. "0000" or "0001"
             let subkey = key.open_subkey(subkey)?;
-            device_id = subkey.get_value("MatchingDeviceId")?;
-            device_id = device_id.split("dev_").last().unwrap().to_string();
-            return Ok(device_id);
+            let device_id: String = subkey.get_value("MatchingDeviceId")?;
+            return PciIds::parse(&device_id).ok_or_else(|| "No matching device found".into());
         }
     }
     Err("No matching device found".into())
 }
 
-pub async fn get_latest_driver_link(
+/// Operating system and language to look up drivers for, see [`xml::get_os_list`] and [`xml::get_language_list`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LookupTarget {
+    pub os: u16,       // osid
+    pub language: u16, // lid
+}
+
+impl Default for LookupTarget {
+    fn default() -> Self {
+        LookupTarget {
+            os: 57,      // Windows 10 64-bit
+            language: 1, // English (US)
+        }
+    }
+}
+
+impl LookupTarget {
+    /// Looks up the ids by name, e.g. ("Windows 11", "Japanese"), keeping the default for `None`
+    pub async fn by_name(
+        os: Option<&str>,
+        language: Option<&str>,
+    ) -> Result<LookupTarget, Box<dyn Error>> {
+        let mut target = LookupTarget::default();
+        if let Some(os) = os {
+            target.os = xml::find_entry(&xml::get_os_list().await?, os)
+                .map_err(|e| format!("Operating system {e}"))?
+                .id;
+        }
+        if let Some(language) = language {
+            target.language = xml::find_entry(&xml::get_language_list().await?, language)
+                .map_err(|e| format!("Language {e}"))?
+                .id;
+        }
+        Ok(target)
+    }
+}
+
+pub async fn get_latest_driver(
     gpu: XmlGpuEntry,
     driver: Driver,
-) -> Result<String, Box<dyn Error>> {
+    target: &LookupTarget,
+    support: &legacy::Support,
+) -> Result<DriverRelease, Box<dyn Error>> {
     let psid = gpu.series;
     let pfid = gpu.id;
+    let osid = target.os;
+    let lid = target.language;
     let dtcid = driver.edition.into_api(); // 1=dch, 0=std
-    let whql = driver.channel.into_api(); // 1 = Game Ready, 4 = Studio
+    let whql = driver
+        .channel
+        .into_api()
+        .ok_or("Only Game Ready and Studio drivers can be looked up")?; // 1 = Game Ready, 4 = Studio
 
-    let link: String = format!("https://www.nvidia.com/Download/processDriver.aspx?psid={psid}&pfid={pfid}&osid=57&lid=1&whql={whql}&dtcid={dtcid}");
+    let link: String = format!("https://www.nvidia.com/Download/processDriver.aspx?psid={psid}&pfid={pfid}&osid={osid}&lid={lid}&whql={whql}&dtcid={dtcid}");
     let link = reqwest::get(link).await?.text().await?;
-    Ok(parse_driver_page(link).await?)
+    let release = parse_driver_page(release::parse_process_driver(&link)?).await?;
+
+    // NVIDIA should only offer drivers that support the product, but don't trust it blindly
+    support.check(&release.version)?;
+    Ok(release)
 }
 
 /// returns direct link to download
-pub async fn parse_driver_page(link: String) -> Result<String, Box<dyn Error>> {
+pub async fn get_latest_driver_link(
+    gpu: XmlGpuEntry,
+    driver: Driver,
+    target: &LookupTarget,
+    support: &legacy::Support,
+) -> Result<String, Box<dyn Error>> {
+    Ok(get_latest_driver(gpu, driver, target, support)
+        .await?
+        .download_url)
+}
+
+/// Fetches and parses a driver details page
+pub async fn parse_driver_page(link: String) -> Result<DriverRelease, Box<dyn Error>> {
     let html = reqwest::get(link).await?.text().await?;
-    let html = html
-        .split("?url=")
-        .last()
-        .unwrap()
-        .split('&')
-        .next()
-        .unwrap();
-    Ok(format!("{BASE_LINK}{html}"))
+    Ok(DriverRelease::parse(&html)?)
 }
 
 pub async fn download(link: String) -> Result<(), Box<dyn Error>> {
@@ -362,7 +890,8 @@ pub async fn download(link: String) -> Result<(), Box<dyn Error>> {
 }
 
 // note: I have tried every rust archive library and other workarounds in order to not use external dependencies without luck.. feel free to suggest a different way!
-pub async fn extract() -> Result<(), Box<dyn Error>> {
+/// Extracts an installer to `TMP_EXTRACT_DIR`, wherever it came from (see [`source::DriverSource::acquire`])
+pub async fn extract(installer: &Path) -> Result<(), Box<dyn Error>> {
     println!("Extracting driver! Please wait...");
 
     // download 7z
@@ -382,8 +911,11 @@ pub async fn extract() -> Result<(), Box<dyn Error>> {
         .arg("-bso0")
         .arg("-bse1")
         .arg("-bsp1")
-        .arg(crate::TMP_FILE.clone())
+        .arg(installer)
         .arg(format!("-o{}", crate::TMP_EXTRACT_DIR.as_path().display()));
-    command.spawn().unwrap().wait().unwrap(); // TODO: handle unwraps
-    Ok(())
+    let status = command.status()?;
+    match status.success() {
+        true => Ok(()),
+        false => Err(format!("Extracting {} failed ({status})", installer.display()).into()),
+    }
 }
@@ -0,0 +1,58 @@
+//! "Latest" discovery for the channels that have no entry in the lookup API.
+//! Vulkan beta drivers are listed on the Vulkan driver page of the developer site,
+//! hotfixes are only announced in support articles, so they are found by scanning past the latest Game Ready driver.
+
+use std::{error::Error, time::Duration};
+
+use once_cell::sync::Lazy;
+use regex::Regex;
+
+use super::{
+    scan::{self, ScanHit, ScanOptions},
+    DriverChannels, DriverEdition, DriverPlatform, DriverVersion,
+};
+
+pub const VULKAN_BETA_LINK: &str = "https://developer.nvidia.com";
+const VULKAN_DRIVER_PAGE: &str = "https://developer.nvidia.com/vulkan-driver";
+
+static REGEX_VULKAN_BETA: Lazy<Regex> =
+    Lazy::new(|| Regex::new("vulkan-beta-([0-9]{3})([0-9]{2})-windows").unwrap());
+
+/// Newest Windows Vulkan beta linked from a page, with its link
+pub fn parse_vulkan_driver_page(html: &str) -> Option<(DriverVersion, String)> {
+    REGEX_VULKAN_BETA
+        .captures_iter(html)
+        .filter_map(|capture| {
+            let version = DriverVersion::new(capture[1].parse().ok()?, capture[2].parse().ok()?);
+            Some((
+                version,
+                format!("{VULKAN_BETA_LINK}/downloads/{}", &capture[0]),
+            ))
+        })
+        .max_by_key(|(version, _)| *version)
+}
+
+pub async fn latest_vulkan_beta() -> Result<(DriverVersion, String), Box<dyn Error>> {
+    let html = reqwest::get(VULKAN_DRIVER_PAGE).await?.text().await?;
+    parse_vulkan_driver_page(&html).ok_or_else(|| "No Vulkan beta driver found".into())
+}
+
+/// Newest hotfix of the branch of `after` (usually the latest Game Ready driver) or the next one
+pub async fn latest_hotfix(after: DriverVersion) -> Result<ScanHit, Box<dyn Error>> {
+    let mut options = ScanOptions {
+        concurrency: 8,
+        delay: Duration::from_millis(100),
+        minors: after.minor..=99,
+        channels: vec![DriverChannels::Hotfix],
+        platforms: vec![DriverPlatform::Desktop],
+        editions: vec![DriverEdition::DCH],
+    };
+
+    let mut hits = scan::scan(after.major, &options).await;
+    if hits.is_empty() {
+        options.minors = 0..=99;
+        hits = scan::scan(after.major + 1, &options).await;
+    }
+    hits.pop()
+        .ok_or_else(|| format!("No hotfix found after {after}").into())
+}
@@ -0,0 +1,136 @@
+//! CUDA toolkit compatibility of driver versions.
+//! Minimum Windows driver of each toolkit release, from the CUDA release notes (toolkit GA releases).
+
+use std::error::Error;
+
+use super::{
+    legacy::DriverBranch, release::DriverRelease, source::AjaxDriverService, DriverVersion,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct CudaVersion {
+    pub major: u8,
+    pub minor: u8,
+}
+
+impl CudaVersion {
+    pub const fn new(major: u8, minor: u8) -> CudaVersion {
+        CudaVersion { major, minor }
+    }
+}
+
+impl std::str::FromStr for CudaVersion {
+    type Err = Box<dyn Error>;
+
+    /// Accepts "12.2" and "12.2.1", the patch version doesn't change the driver requirement
+    fn from_str(version: &str) -> Result<Self, Self::Err> {
+        let mut parts = version.trim().split('.');
+        let major = parts.next().unwrap_or_default().parse()?;
+        let minor = parts.next().unwrap_or("0").parse()?;
+        Ok(CudaVersion { major, minor })
+    }
+}
+
+impl std::fmt::Display for CudaVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}.{}", self.major, self.minor)
+    }
+}
+
+const fn release(cuda: (u8, u8), driver: (u16, u16)) -> (CudaVersion, DriverVersion) {
+    (
+        CudaVersion::new(cuda.0, cuda.1),
+        DriverVersion::new(driver.0, driver.1),
+    )
+}
+
+/// CUDA release -> minimum Windows driver, oldest first
+pub const CUDA_RELEASES: [(CudaVersion, DriverVersion); 25] = [
+    release((9, 0), (385, 54)),
+    release((9, 1), (388, 19)),
+    release((9, 2), (397, 44)),
+    release((10, 0), (411, 31)),
+    release((10, 1), (418, 96)),
+    release((10, 2), (441, 22)),
+    release((11, 0), (451, 48)),
+    release((11, 1), (456, 38)),
+    release((11, 2), (460, 82)),
+    release((11, 3), (465, 89)),
+    release((11, 4), (471, 11)),
+    release((11, 5), (496, 4)),
+    release((11, 6), (511, 23)),
+    release((11, 7), (516, 1)),
+    release((11, 8), (520, 6)),
+    release((12, 0), (527, 41)),
+    release((12, 1), (531, 14)),
+    release((12, 2), (536, 25)),
+    release((12, 3), (545, 84)),
+    release((12, 4), (551, 61)),
+    release((12, 5), (555, 85)),
+    release((12, 6), (560, 76)),
+    release((12, 8), (570, 65)),
+    release((12, 9), (576, 2)),
+    release((13, 0), (580, 88)),
+];
+
+/// Minimum driver for a CUDA release. Releases missing from the table (12.7) get the one of the
+/// nearest older release of the same major version, `None` if there is none or it's newer than the table.
+pub fn min_driver(cuda: CudaVersion) -> Option<DriverVersion> {
+    let (newest, _) = CUDA_RELEASES[CUDA_RELEASES.len() - 1];
+    if cuda > newest {
+        return None;
+    }
+    CUDA_RELEASES
+        .iter()
+        .rev()
+        .find(|(release, _)| release.major == cuda.major && *release <= cuda)
+        .map(|(_, driver)| *driver)
+}
+
+/// Newest CUDA release a driver supports
+pub fn max_cuda(version: DriverVersion) -> Option<CudaVersion> {
+    CUDA_RELEASES
+        .iter()
+        .rev()
+        .find(|(_, driver)| *driver <= version)
+        .map(|(cuda, _)| *cuda)
+}
+
+/// Newest CUDA release any driver of a branch supports, e.g. R535 -> 12.2
+pub fn max_cuda_for_branch(branch: DriverBranch) -> Option<CudaVersion> {
+    max_cuda(branch.last_version())
+}
+
+/// Newest of `releases` (newest first) which supports `cuda` and the GPU
+pub fn pick_release(
+    releases: Vec<DriverRelease>,
+    cuda: CudaVersion,
+    support: &super::legacy::Support,
+) -> Result<DriverRelease, Box<dyn Error>> {
+    let min = min_driver(cuda).ok_or_else(|| format!("Unknown CUDA release {cuda}"))?;
+    if let super::legacy::Support::Legacy(branch) = support {
+        if branch.last_version() < min {
+            let max = max_cuda_for_branch(*branch)
+                .map_or("none".to_string(), |max| format!("up to {max}"));
+            return Err(format!(
+                "This GPU is stuck on {branch}, which supports CUDA {max}, not {cuda}"
+            )
+            .into());
+        }
+    }
+    releases
+        .into_iter()
+        .find(|release| release.version >= min && support.allows(&release.version))
+        .ok_or_else(|| {
+            format!("No driver for this GPU supports CUDA {cuda} (needs {min} or newer)").into()
+        })
+}
+
+/// Latest driver of the query's channel that supports a CUDA release, e.g. the latest Studio driver for CUDA 12.2
+pub async fn latest_supporting(
+    service: &AjaxDriverService,
+    cuda: CudaVersion,
+) -> Result<DriverRelease, Box<dyn Error>> {
+    let releases = service.releases(20).await?;
+    pick_release(releases, cuda, &service.query.support)
+}
@@ -0,0 +1,74 @@
+//! Offline device database from NVIDIA's `supported-gpus.json`.
+//! The file ships with the Linux driver packages (`supported-gpus/supported-gpus.json`) and maps PCI ids to
+//! marketing names and the legacy branch a card is stuck on, if any.
+
+use std::{error::Error, path::Path};
+
+use serde::Deserialize;
+
+#[derive(Debug, Deserialize)]
+pub struct SupportedGpus {
+    pub chips: Vec<Chip>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Chip {
+    pub devid: String, // e.g. "0x2204"
+    #[serde(default, alias = "subdeviceid")]
+    pub subdevid: Option<String>,
+    #[serde(default)]
+    pub subvendorid: Option<String>,
+    pub name: String, // e.g. "NVIDIA GeForce RTX 3090"
+    #[serde(default)]
+    pub legacybranch: Option<String>, // e.g. "470.xx", missing for cards supported by current drivers
+}
+
+impl Chip {
+    /// Name without the "NVIDIA " prefix, the same format as pci.ids
+    pub fn name(&self) -> &str {
+        self.name.strip_prefix("NVIDIA ").unwrap_or(&self.name)
+    }
+}
+
+/// "0x1E04" -> "1e04", the format returned by [`super::get_gpu_id`]
+fn normalise_id(id: &str) -> String {
+    let id = id.trim();
+    id.strip_prefix("0x")
+        .or_else(|| id.strip_prefix("0X"))
+        .unwrap_or(id)
+        .to_ascii_lowercase()
+}
+
+impl SupportedGpus {
+    pub fn parse(json: &str) -> Result<SupportedGpus, Box<dyn Error>> {
+        Ok(serde_json::from_str(json)?)
+    }
+
+    pub fn from_file(path: &Path) -> Result<SupportedGpus, Box<dyn Error>> {
+        SupportedGpus::parse(&std::fs::read_to_string(path)?)
+    }
+
+    /// Finds a chip by device id. Entries with a matching subsystem (`subvendor`, `subdevice`) win over generic ones,
+    /// as some cards share a device id but not a name.
+    pub fn lookup(&self, device_id: &str, subsystem: Option<(&str, &str)>) -> Option<&Chip> {
+        let device_id = normalise_id(device_id);
+        let mut generic: Option<&Chip> = None;
+        for chip in self
+            .chips
+            .iter()
+            .filter(|chip| normalise_id(&chip.devid) == device_id)
+        {
+            match (&chip.subvendorid, &chip.subdevid, subsystem) {
+                (Some(vendor), Some(device), Some((sub_vendor, sub_device)))
+                    if normalise_id(vendor) == normalise_id(sub_vendor)
+                        && normalise_id(device) == normalise_id(sub_device) =>
+                {
+                    return Some(chip);
+                }
+                (None, None, _) if generic.is_none() => generic = Some(chip),
+                _ => {}
+            }
+        }
+        generic
+    }
+}
@@ -0,0 +1,129 @@
+//! Older GPUs are stuck on the last driver branch that supported them, e.g. Kepler on R470 (474.xx).
+//! Current drivers don't install on them, so lookups and links are limited to that branch.
+
+use std::error::Error;
+
+use super::{DetectedGpu, DriverVersion};
+
+/// A driver branch such as R470, which covers the releases 470.xx to 474.xx
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct DriverBranch(pub u16);
+
+impl DriverBranch {
+    // Windows releases of a branch use the next few majors, e.g. R515 -> 516.xx
+    const SPAN: u16 = 5;
+
+    /// The newest version a branch could contain
+    pub fn last_version(&self) -> DriverVersion {
+        DriverVersion::new(self.0 + Self::SPAN - 1, 99)
+    }
+}
+
+impl std::str::FromStr for DriverBranch {
+    type Err = Box<dyn Error>;
+
+    /// Accepts "R470", "470" and "470.xx" (the format used by supported-gpus.json)
+    fn from_str(branch: &str) -> Result<Self, Self::Err> {
+        let branch = branch.trim().trim_start_matches(['R', 'r']);
+        let major = branch.split('.').next().unwrap_or(branch);
+        Ok(DriverBranch(major.parse()?))
+    }
+}
+
+impl std::fmt::Display for DriverBranch {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "R{}", self.0)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Support {
+    Current,              // supported by current drivers
+    Legacy(DriverBranch), // last supported by this branch
+}
+
+impl Support {
+    /// Prefers the legacy branch from supported-gpus.json, otherwise guesses from the name
+    pub fn of(gpu: &DetectedGpu) -> Support {
+        match gpu.legacy_branch.as_deref().map(str::parse) {
+            Some(Ok(branch)) => Support::Legacy(branch),
+            _ => Support::from_name(&gpu.name),
+        }
+    }
+
+    /// Guesses the last supported branch from a product name, e.g. "GeForce GTX 680" -> R470.
+    /// Some names were used for cards of different architectures (GT 730 is either Fermi or Kepler),
+    /// these are assumed to be the newer one.
+    pub fn from_name(name: &str) -> Support {
+        let name = super::resolve::normalise(name);
+        let family = |word: &str| name.family.iter().any(|w| w == word);
+
+        // Maxwell, Pascal and Volta ended with R580
+        const R580: Support = Support::Legacy(DriverBranch(580));
+        const R470: Support = Support::Legacy(DriverBranch(470)); // Kepler
+        const R390: Support = Support::Legacy(DriverBranch(390)); // Fermi
+        const R340: Support = Support::Legacy(DriverBranch(340)); // Tesla
+
+        if family("titan") && !family("rtx") {
+            return match family("x") || family("xp") || family("v") {
+                true => R580,
+                false => R470, // TITAN, TITAN Black, TITAN Z
+            };
+        }
+
+        for model in &name.models {
+            // Quadro K2000, M4000, P5000, GV100...
+            if family("quadro") {
+                match model.chars().next() {
+                    Some('k') if ["k620", "k1200", "k2200"].contains(&model.as_str()) => {
+                        return R580
+                    }
+                    Some('k') => return R470,
+                    Some('m') | Some('p') | Some('g') => return R580,
+                    _ => continue,
+                }
+            }
+
+            let digits: String = model.chars().filter(char::is_ascii_digit).collect();
+            let number: u16 = match digits.parse() {
+                Ok(number) => number,
+                Err(_) => continue,
+            };
+            if model.starts_with("mx") {
+                // MX110 to MX350 are Maxwell or Pascal
+                return if number < 400 { R580 } else { Support::Current };
+            }
+            if !(family("geforce") || family("gtx") || family("gt") || family("gts")) {
+                continue;
+            }
+            return match number {
+                8000..=9999 => R340, // GeForce 8 and 9 series
+                100..=399 => R340,
+                400..=599 => R390,
+                745 | 750 => R580, // first generation Maxwell
+                600..=799 => R470,
+                800..=1099 => R580, // 800M and 900 series, GTX 10 series
+                _ => Support::Current,
+            };
+        }
+        Support::Current
+    }
+
+    pub fn allows(&self, version: &DriverVersion) -> bool {
+        match self {
+            Support::Current => true,
+            Support::Legacy(branch) => *version <= branch.last_version(),
+        }
+    }
+
+    pub fn check(&self, version: &DriverVersion) -> Result<(), Box<dyn Error>> {
+        match self {
+            Support::Legacy(branch) if !self.allows(version) => Err(format!(
+                "Driver {version} is beyond support for this GPU, the last branch supporting it is {branch} (up to {}.xx)",
+                branch.last_version().major
+            )
+            .into()),
+            _ => Ok(()),
+        }
+    }
+}
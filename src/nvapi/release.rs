@@ -0,0 +1,231 @@
+//! Parsing of `processDriver.aspx` results and the driver details page they point to,
+//! e.g. <https://www.nvidia.com/download/driverResults.aspx/193095/en-us>
+
+use once_cell::sync::Lazy;
+use regex::Regex;
+
+use super::{
+    resolve::{normalise, NormalisedName},
+    xml::{is_notebook_series, XmlGpuEntry},
+    DriverChannels, DriverVersion, BASE_LINK, REGEX_VERSION,
+};
+
+static REGEX_TAG: Lazy<Regex> = Lazy::new(|| Regex::new("<[^>]*>").unwrap());
+static REGEX_LINE_BREAK: Lazy<Regex> =
+    Lazy::new(|| Regex::new("<[bB][rR][^>]*>|</[pP]>|</li>|</h[1-6]>").unwrap());
+static REGEX_DOWNLOAD_BUTTON: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r#"<a[^>]*id="lnkDwnldBtn"[^>]*>"#).unwrap());
+static REGEX_HREF: Lazy<Regex> = Lazy::new(|| Regex::new(r#"href="([^"]*)""#).unwrap());
+static REGEX_URL_PARAM: Lazy<Regex> = Lazy::new(|| Regex::new(r"[?&]url=([^&]+)").unwrap());
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DriverRelease {
+    pub version: DriverVersion,
+    pub release_date: Option<String>, // e.g. "2022.8.9"
+    pub file_size: Option<String>,    // e.g. "815.98 MB"
+    pub channel: DriverChannels,
+    pub os: Option<String>, // e.g. "Windows 10 64-bit, Windows 11"
+    pub highlights: String,
+    pub supported_products: Vec<SupportedProduct>,
+    pub download_url: String,
+}
+
+/// A product from the "Supported Products" tab of a driver page
+#[derive(Debug, Clone, PartialEq)]
+pub struct SupportedProduct {
+    pub series: Option<String>, // e.g. "GeForce RTX 30 Series (Notebooks)"
+    pub name: String,           // e.g. "GeForce RTX 3070 Laptop GPU"
+}
+
+impl SupportedProduct {
+    /// Whether this is the same product as a lookup entry, see [`normalise`]
+    pub fn matches(&self, product: &XmlGpuEntry) -> bool {
+        let (ours, theirs) = (normalise(&self.name), normalise(&product.name));
+        // Names without a model number ("TITAN RTX", "TITAN Xp") have to match as a whole
+        if ours.models.is_empty() || theirs.models.is_empty() {
+            return ours == theirs;
+        }
+        if ours.models != theirs.models || ours.suffixes != theirs.suffixes {
+            return false;
+        }
+        // "Quadro RTX 4000" isn't an "RTX 4000", but a name may leave out the brand
+        let within = |a: &NormalisedName, b: &NormalisedName| {
+            a.family.iter().all(|word| b.family.contains(word))
+        };
+        if !within(&ours, &theirs) && !within(&theirs, &ours) {
+            return false;
+        }
+        // Older notebook GPUs have the same name as the desktop ones, only the series tells them apart
+        let notebook = |name: &NormalisedName, series: Option<&str>| {
+            name.mobile || series.is_some_and(is_notebook_series)
+        };
+        notebook(&ours, self.series.as_deref()) == notebook(&theirs, product.series_name.as_deref())
+    }
+}
+
+/// The page didn't look like we expected, most likely NVIDIA changed it
+#[derive(Debug, Clone, PartialEq)]
+pub enum DriverPageError {
+    NoDriverFound,               // processDriver found nothing for the query
+    UnknownResult(String),       // processDriver returned something that isn't a details link
+    UnknownLayout(&'static str), // a required part of the details page is missing
+}
+
+impl std::fmt::Display for DriverPageError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            DriverPageError::NoDriverFound => write!(f, "No driver found for this product"),
+            DriverPageError::UnknownResult(result) => {
+                write!(f, "Unexpected driver lookup result: \"{result}\"")
+            }
+            DriverPageError::UnknownLayout(part) => {
+                write!(f, "Unknown driver page layout, couldn't find the {part}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for DriverPageError {}
+
+/// processDriver.aspx answers with the link to the details page, e.g. "//www.nvidia.com/download/driverResults.aspx/193095/en-us"
+pub fn parse_process_driver(result: &str) -> Result<String, DriverPageError> {
+    let result = result.trim();
+    if result.is_empty() || result.contains("No certified downloads") {
+        return Err(DriverPageError::NoDriverFound);
+    }
+    if !result.contains("driverResults.aspx") || result.contains(char::is_whitespace) {
+        return Err(DriverPageError::UnknownResult(
+            result.chars().take(100).collect(),
+        ));
+    }
+    Ok(match result {
+        link if link.starts_with("//") => format!("https:{link}"),
+        link if link.starts_with('/') => format!("https://www.nvidia.com{link}"),
+        link => link.to_string(),
+    })
+}
+
+/// Decodes the few entities NVIDIA uses
+fn decode_entities(text: &str) -> String {
+    text.replace("&nbsp;", " ")
+        .replace("&#160;", " ")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&amp;", "&")
+}
+
+/// Plain text of some html, with one line per paragraph, list item or line break
+pub(crate) fn html_to_text(html: &str) -> String {
+    let text = REGEX_LINE_BREAK.replace_all(html, "\n");
+    let text = decode_entities(&REGEX_TAG.replace_all(&text, ""));
+    text.lines()
+        .map(|line| line.split_whitespace().collect::<Vec<&str>>().join(" "))
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+/// Inner html of the first element with the given id
+pub(crate) fn element_by_id<'a>(html: &'a str, tag: &str, id: &str) -> Option<&'a str> {
+    let start = html.find(&format!("id=\"{id}\""))?;
+    let start = start + html[start..].find('>')? + 1;
+    let end = start + html[start..].find(&format!("</{tag}>"))?;
+    Some(&html[start..end])
+}
+
+fn text_by_id(html: &str, tag: &str, id: &str) -> Option<String> {
+    element_by_id(html, tag, id)
+        .map(html_to_text)
+        .filter(|text| !text.is_empty())
+}
+
+fn download_url(html: &str) -> Option<String> {
+    // Prefer the download button, then any confirmation link on the page
+    let href = REGEX_DOWNLOAD_BUTTON
+        .find(html)
+        .and_then(|button| REGEX_HREF.captures(button.as_str()))
+        .map(|href| href[1].to_string());
+    let url = href
+        .as_deref()
+        .and_then(|href| REGEX_URL_PARAM.captures(href))
+        .or_else(|| REGEX_URL_PARAM.captures(html))
+        .map(|url| decode_entities(&url[1]))?;
+
+    Some(match url {
+        url if url.starts_with("http") => url,
+        url if url.starts_with('/') => format!("{BASE_LINK}{url}"),
+        _ => return None,
+    })
+}
+
+/// Supported products are grouped by series, e.g. "GeForce RTX 30 Series:" followed by a comma separated list
+pub fn supported_products(html: &str) -> Vec<SupportedProduct> {
+    let text = match element_by_id(html, "div", "tab2_content") {
+        Some(products) => html_to_text(products),
+        None => return Vec::new(),
+    };
+
+    let mut products: Vec<SupportedProduct> = Vec::new();
+    let mut series: Option<String> = None;
+    for line in text.lines() {
+        let (heading, list) = match line.split_once(':') {
+            Some((heading, list)) => (Some(heading.trim()), list),
+            None => (None, line),
+        };
+        if let Some(heading) = heading {
+            series = Some(heading.to_string());
+        }
+        for product in list.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+            let product = product.strip_prefix("NVIDIA ").unwrap_or(product);
+            products.push(SupportedProduct {
+                series: series.clone(),
+                name: product.to_string(),
+            });
+        }
+    }
+    products
+}
+
+impl DriverRelease {
+    /// Parses a driver details page
+    pub fn parse(html: &str) -> Result<DriverRelease, DriverPageError> {
+        let version = text_by_id(html, "td", "tdVersion")
+            .and_then(|version| REGEX_VERSION.find(&version).map(|v| v.as_str().to_string()))
+            .and_then(|version| version.parse().ok())
+            .ok_or(DriverPageError::UnknownLayout("version"))?;
+        let download_url =
+            download_url(html).ok_or(DriverPageError::UnknownLayout("download link"))?;
+        let channel = match download_url.contains("-nsd") {
+            true => DriverChannels::Studio,
+            false => DriverChannels::GameReady,
+        };
+
+        Ok(DriverRelease {
+            version,
+            release_date: text_by_id(html, "td", "tdReleaseDate"),
+            file_size: text_by_id(html, "td", "tdFileSize"),
+            channel,
+            os: text_by_id(html, "td", "tdOSName"),
+            highlights: text_by_id(html, "div", "tab1_content").unwrap_or_default(),
+            supported_products: supported_products(html),
+            download_url,
+        })
+    }
+
+    /// Whether the driver officially supports a product
+    pub fn supports(&self, product: &XmlGpuEntry) -> bool {
+        self.supported_products
+            .iter()
+            .any(|supported| supported.matches(product))
+    }
+
+    /// The entries of a product list (see [`super::xml::get_gpu_list`]) this driver supports
+    pub fn supported_entries(&self, list: &[XmlGpuEntry]) -> Vec<XmlGpuEntry> {
+        list.iter()
+            .filter(|product| self.supports(product))
+            .cloned()
+            .collect()
+    }
+}
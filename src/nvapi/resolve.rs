@@ -0,0 +1,168 @@
+//! Matching a detected device name to an entry of NVIDIA's product lookup list.
+//! pci.ids and NVIDIA don't agree on naming, e.g. "GeForce RTX 3070 Mobile / Max-Q" (pci.ids)
+//! is "GeForce RTX 3070 Laptop GPU" in the lookup list, so names are normalised before comparing.
+
+use super::{xml, xml::XmlGpuEntry, DriverPlatform};
+
+/// How sure we are that a resolved entry is the detected device.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Confidence {
+    Low,    // model matches, but the rest of the name doesn't agree much
+    Medium, // several entries matched equally well, the first one was picked
+    High,   // one entry matched better than all others
+    Exact,  // identical after normalisation
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct NormalisedName {
+    /// Model numbers, e.g. "3070", "a4000", "mx450"
+    pub models: Vec<String>,
+    /// Model suffixes that make a different card, e.g. "ti", "super"
+    pub suffixes: Vec<String>,
+    /// Everything else, e.g. "geforce", "rtx"
+    pub family: Vec<String>,
+    /// Whether the name marks a mobile part ("Laptop GPU", "Mobile", "Max-Q")
+    pub mobile: bool,
+}
+
+const MOBILE_WORDS: [&str; 5] = ["laptop", "mobile", "max-q", "maxq", "notebook"];
+const SUFFIX_WORDS: [&str; 2] = ["ti", "super"];
+// Words that don't help telling cards apart
+const NOISE_WORDS: [&str; 9] = [
+    "nvidia", "gpu", "graphics", "design", "with", "lite", "hash", "rate", "lhr",
+];
+
+pub fn normalise(name: &str) -> NormalisedName {
+    let lower = name
+        .to_ascii_lowercase()
+        .replace("max q", "max-q")
+        .replace("notebooks", "notebook");
+    let mut normalised = NormalisedName {
+        models: Vec::new(),
+        suffixes: Vec::new(),
+        family: Vec::new(),
+        mobile: false,
+    };
+
+    for word in lower.split(|c: char| c.is_whitespace() || "/,()[]".contains(c)) {
+        if word.is_empty() || NOISE_WORDS.contains(&word) {
+            continue;
+        }
+        if MOBILE_WORDS.contains(&word) {
+            normalised.mobile = true;
+            continue;
+        }
+        // memory size variants ("12gb") aren't part of NVIDIA's product names
+        if word.ends_with("gb") && word[..word.len() - 2].chars().all(|c| c.is_ascii_digit()) {
+            continue;
+        }
+        if SUFFIX_WORDS.contains(&word) {
+            normalised.suffixes.push(word.to_string());
+            continue;
+        }
+        if word.chars().any(|c| c.is_ascii_digit()) {
+            // split glued suffixes, e.g. "3080ti" or "2060super"
+            match SUFFIX_WORDS
+                .iter()
+                .find(|suffix| word.len() > suffix.len() && word.ends_with(*suffix))
+            {
+                Some(suffix) => {
+                    normalised
+                        .models
+                        .push(word[..word.len() - suffix.len()].to_string());
+                    normalised.suffixes.push(suffix.to_string());
+                }
+                None => normalised.models.push(word.to_string()),
+            }
+            continue;
+        }
+        normalised.family.push(word.to_string());
+    }
+
+    normalised.models.sort();
+    normalised.suffixes.sort();
+    normalised.suffixes.dedup();
+    normalised.family.sort();
+    normalised.family.dedup();
+    normalised
+}
+
+/// Scores how well `candidate` describes `detected`, `None` if it can't be the same card.
+fn score(detected: &NormalisedName, candidate: &NormalisedName) -> Option<u32> {
+    // A 3070 is not a 3070 Ti, so models and suffixes have to match exactly
+    if detected.models.is_empty()
+        || detected.models != candidate.models
+        || detected.suffixes != candidate.suffixes
+    {
+        return None;
+    }
+
+    let shared = detected
+        .family
+        .iter()
+        .filter(|word| candidate.family.contains(word))
+        .count() as u32;
+    let total = (detected.family.len() + candidate.family.len()) as u32 - shared;
+    // family overlap out of 100, mobile agreement is worth a bit more than one shared word
+    let mut score = (shared * 100).checked_div(total).unwrap_or(100);
+    if detected.mobile == candidate.mobile {
+        score += 50;
+    }
+    Some(score)
+}
+
+/// Finds the entry of `list` which best matches a device `name`, e.g. the one returned by [`super::detect_gpu`].
+/// `platform` overrides whether the device is a mobile part, if it's known from more than the name.
+pub fn resolve_gpu(
+    name: &str,
+    platform: Option<DriverPlatform>,
+    list: &[XmlGpuEntry],
+) -> Option<(XmlGpuEntry, Confidence)> {
+    let mut detected = normalise(name);
+    if let Some(platform) = platform {
+        detected.mobile = platform == DriverPlatform::Notebook;
+    }
+
+    let mut best: Option<(&XmlGpuEntry, u32, NormalisedName)> = None;
+    let mut tied = false;
+    for entry in list {
+        let mut candidate = normalise(&entry.name);
+        // Older mobile GPUs are listed as e.g. "GeForce GTX 1060" in a notebook series
+        if entry
+            .series_name
+            .as_deref()
+            .is_some_and(xml::is_notebook_series)
+        {
+            candidate.mobile = true;
+        }
+        let score = match score(&detected, &candidate) {
+            Some(score) => score,
+            None => continue,
+        };
+        match &best {
+            Some((best_entry, best_score, _)) if score == *best_score => {
+                // The lookup list contains some products twice, that's not a real tie
+                if best_entry.name != entry.name {
+                    tied = true;
+                }
+            }
+            Some((_, best_score, _)) if score < *best_score => {}
+            _ => {
+                best = Some((entry, score, candidate));
+                tied = false;
+            }
+        }
+    }
+
+    let (entry, score, candidate) = best?;
+    let confidence = if candidate == detected {
+        Confidence::Exact
+    } else if tied {
+        Confidence::Medium
+    } else if score >= 125 {
+        Confidence::High
+    } else {
+        Confidence::Low
+    };
+    Some((entry.clone(), confidence))
+}
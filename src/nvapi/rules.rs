@@ -0,0 +1,63 @@
+//! Combinations of driver options that can never exist, so they're rejected without asking NVIDIA.
+//! Based on when NVIDIA introduced or dropped each kind of package.
+
+use super::{Driver, DriverChannels, DriverEdition, DriverVersion, DriverWindowsVersion};
+
+/// First DCH packages were released with R410
+const FIRST_DCH: DriverVersion = DriverVersion::new(411, 0);
+/// Standard packages were dropped after R470
+const LAST_STD: DriverVersion = DriverVersion::new(474, 99);
+/// First Studio ("Creator Ready") driver
+const FIRST_STUDIO: DriverVersion = DriverVersion::new(419, 67);
+/// Packages are named "win10-win11" since Windows 11 was announced
+const FIRST_WIN11: DriverVersion = DriverVersion::new(471, 0);
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct InvalidCombination(pub String);
+
+impl std::fmt::Display for InvalidCombination {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "Impossible driver combination: {}", self.0)
+    }
+}
+
+impl std::error::Error for InvalidCombination {}
+
+fn invalid(reason: String) -> Result<(), InvalidCombination> {
+    Err(InvalidCombination(reason))
+}
+
+/// Checks channel and edition against the version
+pub fn check(driver: &Driver, version: DriverVersion) -> Result<(), InvalidCombination> {
+    match (driver.channel, driver.edition) {
+        (_, DriverEdition::DCH) if version < FIRST_DCH => invalid(format!(
+            "DCH packages don't exist before {FIRST_DCH}, {version} is Standard only"
+        )),
+        (_, DriverEdition::STD) if version > LAST_STD => invalid(format!(
+            "Standard packages were discontinued after R470, {version} is DCH only"
+        )),
+        (DriverChannels::Studio, _) if version < FIRST_STUDIO => invalid(format!(
+            "Studio drivers started with {FIRST_STUDIO}, {version} is older"
+        )),
+        (DriverChannels::Hotfix, DriverEdition::STD) => {
+            invalid("hotfix drivers are DCH only".to_string())
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Checks the Windows version naming against the version.
+/// Vulkan betas are never named "win10-win11", their main link is tagged Win11 for every version.
+pub fn check_windows(
+    driver: &Driver,
+    version: DriverVersion,
+    windows: DriverWindowsVersion,
+) -> Result<(), InvalidCombination> {
+    match (driver.channel, windows) {
+        (DriverChannels::VulkanBeta, _) => Ok(()),
+        (_, DriverWindowsVersion::Win11) if version < FIRST_WIN11 => invalid(format!(
+            "packages are only named \"win10-win11\" since {FIRST_WIN11}"
+        )),
+        _ => Ok(()),
+    }
+}
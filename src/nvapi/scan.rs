@@ -0,0 +1,238 @@
+//! Finds builds that never show up in the lookup pages (hotfixes, OEM-only or pulled releases) by trying
+//! every version of a branch against the link templates of [`super::candidate_links`],
+//! and probes which variants (channel, platform, edition, Windows version) exist for a version.
+
+use std::{sync::Arc, time::Duration};
+
+use serde::Serialize;
+use tokio::sync::{Mutex, Semaphore};
+
+use super::{
+    candidate_links, Driver, DriverChannels, DriverEdition, DriverPlatform, DriverVersion,
+    DriverWindowsVersion,
+};
+
+pub struct ScanOptions {
+    pub concurrency: usize, // requests in flight at once
+    pub delay: Duration,    // minimum time between two requests
+    pub minors: std::ops::RangeInclusive<u16>,
+    pub channels: Vec<DriverChannels>,
+    pub platforms: Vec<DriverPlatform>,
+    pub editions: Vec<DriverEdition>,
+}
+
+impl Default for ScanOptions {
+    fn default() -> Self {
+        ScanOptions {
+            concurrency: 8,
+            delay: Duration::from_millis(100),
+            minors: 0..=99,
+            channels: vec![DriverChannels::GameReady, DriverChannels::Studio],
+            platforms: vec![DriverPlatform::Desktop, DriverPlatform::Notebook],
+            editions: vec![DriverEdition::DCH, DriverEdition::STD],
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScanHit {
+    pub version: DriverVersion,
+    pub channel: DriverChannels,
+    pub platform: DriverPlatform,
+    pub edition: DriverEdition,
+    pub windows: DriverWindowsVersion,
+    pub link: String,
+}
+
+impl std::fmt::Display for ScanHit {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {:?} {:?} {:?} {:?}: {}",
+            self.version, self.platform, self.channel, self.edition, self.windows, self.link
+        )
+    }
+}
+
+/// Every driver the scan would try, see [`ScanOptions`]
+pub fn candidates(major: u16, options: &ScanOptions) -> Vec<Driver> {
+    let mut drivers: Vec<Driver> = Vec::new();
+    for minor in options.minors.clone() {
+        for channel in &options.channels {
+            for platform in &options.platforms {
+                for edition in &options.editions {
+                    drivers.push(Driver {
+                        version: DriverVersion::new(major, minor).to_string(),
+                        channel: *channel,
+                        platform: *platform,
+                        edition: *edition,
+                    });
+                }
+            }
+        }
+    }
+    drivers
+}
+
+/// Some channels use the same link for every platform or edition, e.g. hotfixes ("desktop-notebook").
+/// Keeps the first entry of each link so it's only probed once.
+fn dedup_links<T>(entries: &mut Vec<T>, link: impl Fn(&T) -> &str) {
+    let mut seen: Vec<String> = Vec::new();
+    entries.retain(|entry| match seen.iter().any(|seen| seen == link(entry)) {
+        true => false,
+        false => {
+            seen.push(link(entry).to_string());
+            true
+        }
+    });
+}
+
+/// Whether a link exists and how big the file behind it is
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct LinkStatus {
+    pub exists: bool,
+    pub size: Option<u64>, // bytes, if the server said
+}
+
+/// Checks every link with at most `concurrency` requests in flight and at least `delay` between two requests.
+/// The statuses are in the same order as the links.
+pub async fn probe_links(
+    links: Vec<String>,
+    concurrency: usize,
+    delay: Duration,
+) -> Vec<LinkStatus> {
+    let client = reqwest::Client::new();
+    let permits = Arc::new(Semaphore::new(concurrency.max(1)));
+    // Rate limit across all tasks, each request waits for the next tick
+    let mut interval = tokio::time::interval(delay.max(Duration::from_millis(1)));
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    let interval = Arc::new(Mutex::new(interval));
+
+    let tasks: Vec<_> = links
+        .into_iter()
+        .map(|link| {
+            let (client, permits, interval) = (client.clone(), permits.clone(), interval.clone());
+            tokio::spawn(async move {
+                let _permit = permits.acquire().await.ok()?;
+                interval.lock().await.tick().await;
+                // HEAD is enough to know if the file exists, no need to start downloading it
+                let resp = client.head(&link).send().await.ok()?;
+                let size = resp
+                    .headers()
+                    .get(reqwest::header::CONTENT_LENGTH)
+                    .and_then(|length| length.to_str().ok())
+                    .and_then(|length| length.parse().ok());
+                Some(LinkStatus {
+                    exists: resp.status().is_success(),
+                    size,
+                })
+            })
+        })
+        .collect();
+
+    let mut statuses: Vec<LinkStatus> = Vec::new();
+    for task in tasks {
+        statuses.push(task.await.ok().flatten().unwrap_or_default());
+    }
+    statuses
+}
+
+/// The links a scan probes, once each
+pub fn scan_candidates(major: u16, options: &ScanOptions) -> Vec<ScanHit> {
+    let mut hits: Vec<ScanHit> = Vec::new();
+    for driver in candidates(major, options) {
+        let version = match driver.parsed_version() {
+            Ok(version) => version,
+            Err(_) => continue,
+        };
+        for (windows, link) in candidate_links(&driver) {
+            hits.push(ScanHit {
+                version,
+                channel: driver.channel,
+                platform: driver.platform,
+                edition: driver.edition,
+                windows,
+                link,
+            });
+        }
+    }
+    dedup_links(&mut hits, |hit| &hit.link);
+    hits
+}
+
+/// Scans a whole branch, e.g. 516 for R516, and returns the builds that exist ordered by version
+pub async fn scan(major: u16, options: &ScanOptions) -> Vec<ScanHit> {
+    let hits = scan_candidates(major, options);
+    let links = hits.iter().map(|hit| hit.link.clone()).collect();
+    let statuses = probe_links(links, options.concurrency, options.delay).await;
+    let mut hits: Vec<ScanHit> = hits
+        .into_iter()
+        .zip(statuses)
+        .filter(|(_, status)| status.exists)
+        .map(|(hit, _)| hit)
+        .collect();
+    hits.sort_by_key(|hit| hit.version);
+    hits
+}
+
+/// One combination of a version's matrix, see [`probe_matrix`]
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MatrixEntry {
+    pub channel: DriverChannels,
+    pub platform: DriverPlatform,
+    pub edition: DriverEdition,
+    pub windows: DriverWindowsVersion,
+    pub link: String,
+    pub exists: bool,
+    pub size: Option<u64>,
+}
+
+/// Every combination of channel, platform, edition and Windows version a version could be published as.
+/// Vulkan betas are left out, their links are download pages rather than installers.
+pub fn matrix_candidates(version: DriverVersion) -> Vec<MatrixEntry> {
+    let mut entries: Vec<MatrixEntry> = Vec::new();
+    for channel in DriverChannels::iter().filter(|channel| **channel != DriverChannels::VulkanBeta)
+    {
+        for platform in DriverPlatform::iter() {
+            for edition in DriverEdition::iter() {
+                let driver = Driver {
+                    version: version.to_string(),
+                    channel: *channel,
+                    platform: *platform,
+                    edition: *edition,
+                };
+                for (windows, link) in candidate_links(&driver) {
+                    entries.push(MatrixEntry {
+                        channel: *channel,
+                        platform: *platform,
+                        edition: *edition,
+                        windows,
+                        link,
+                        exists: false,
+                        size: None,
+                    });
+                }
+            }
+        }
+    }
+    dedup_links(&mut entries, |entry| &entry.link);
+    entries
+}
+
+/// Probes every combination of a version at once
+pub async fn probe_matrix(
+    version: DriverVersion,
+    concurrency: usize,
+    delay: Duration,
+) -> Vec<MatrixEntry> {
+    let mut entries = matrix_candidates(version);
+    let links = entries.iter().map(|entry| entry.link.clone()).collect();
+    for (entry, status) in entries
+        .iter_mut()
+        .zip(probe_links(links, concurrency, delay).await)
+    {
+        entry.exists = status.exists;
+        entry.size = status.size;
+    }
+    entries
+}
@@ -0,0 +1,331 @@
+//! Places to get a driver from. The NVIDIA website scrapes `processDriver.aspx` and the driver details page,
+//! the AjaxDriverService is the JSON API used by GeForce Experience. Both are queried with the same psid/pfid/osid.
+//! An installer can also come from a direct link or a local file, e.g. a package on a network share.
+//! Whatever the source, [`DriverSource::acquire`] gives a local installer for [`super::extract`].
+
+use std::{cell::RefCell, error::Error, path::PathBuf};
+
+use async_trait::async_trait;
+use serde::Deserialize;
+
+use super::{
+    legacy::Support,
+    release::{html_to_text, DriverRelease},
+    xml::XmlGpuEntry,
+    Driver, DriverChannels, DriverVersion, LookupTarget,
+};
+
+const AJAX_DRIVER_SERVICE: &str =
+    "https://gfwsl.geforce.com/services_toolkit/services/com/nvidia/services/AjaxDriverService.php";
+
+/// What to look up the latest driver for
+#[derive(Debug, Clone)]
+pub struct DriverQuery {
+    pub gpu: XmlGpuEntry,
+    pub driver: Driver, // channel and edition, the version is ignored
+    pub target: LookupTarget,
+    pub support: Support,
+}
+
+#[async_trait(?Send)]
+pub trait DriverSource {
+    /// Shown in messages, e.g. "NVIDIA website"
+    fn name(&self) -> &'static str;
+
+    async fn release(&self) -> Result<DriverRelease, Box<dyn Error>>;
+
+    /// Makes the installer available locally and returns its path, downloading to `TMP_FILE` by default
+    async fn acquire(&self) -> Result<PathBuf, Box<dyn Error>> {
+        let release = self.release().await?;
+        super::download(release.download_url).await?;
+        Ok(crate::TMP_FILE.to_path_buf())
+    }
+}
+
+/// A direct link for "http(s)://" arguments, a local file otherwise
+pub fn from_arg(arg: &str) -> Box<dyn DriverSource> {
+    match arg.starts_with("http://") || arg.starts_with("https://") {
+        true => Box::new(DirectUrl(arg.to_string())),
+        false => Box::new(LocalPath(PathBuf::from(arg))),
+    }
+}
+
+/// Metadata of an installer we only know the location of
+fn unlisted_release(
+    version: Option<DriverVersion>,
+    location: String,
+) -> Result<DriverRelease, Box<dyn Error>> {
+    let version =
+        version.ok_or_else(|| format!("Couldn't find a driver version in \"{location}\""))?;
+    Ok(DriverRelease {
+        version,
+        release_date: None,
+        file_size: None,
+        channel: match location.contains("-nsd") {
+            true => DriverChannels::Studio,
+            false => DriverChannels::GameReady,
+        },
+        os: None,
+        highlights: String::new(),
+        supported_products: Vec::new(),
+        download_url: location,
+    })
+}
+
+/// A download link, e.g. one shared by a colleague
+pub struct DirectUrl(pub String);
+
+#[async_trait(?Send)]
+impl DriverSource for DirectUrl {
+    fn name(&self) -> &'static str {
+        "direct link"
+    }
+
+    async fn release(&self) -> Result<DriverRelease, Box<dyn Error>> {
+        let name = self.0.rsplit('/').next().unwrap_or_default();
+        unlisted_release(DriverVersion::from_file_name(name), self.0.clone())
+    }
+
+    // No need for a version to download
+    async fn acquire(&self) -> Result<PathBuf, Box<dyn Error>> {
+        super::download(self.0.clone()).await?;
+        Ok(crate::TMP_FILE.to_path_buf())
+    }
+}
+
+/// An installer that's already on disk, used in place
+pub struct LocalPath(pub PathBuf);
+
+#[async_trait(?Send)]
+impl DriverSource for LocalPath {
+    fn name(&self) -> &'static str {
+        "local file"
+    }
+
+    async fn release(&self) -> Result<DriverRelease, Box<dyn Error>> {
+        let version = self
+            .0
+            .file_name()
+            .and_then(|name| DriverVersion::from_file_name(&name.to_string_lossy()));
+        unlisted_release(version, self.0.display().to_string())
+    }
+
+    async fn acquire(&self) -> Result<PathBuf, Box<dyn Error>> {
+        match self.0.is_file() {
+            true => Ok(self.0.clone()),
+            false => Err(format!("Installer \"{}\" not found", self.0.display()).into()),
+        }
+    }
+}
+
+/// Scrapes the NVIDIA website, see [`super::get_latest_driver`]
+pub struct NvidiaWeb(pub DriverQuery);
+
+#[async_trait(?Send)]
+impl DriverSource for NvidiaWeb {
+    fn name(&self) -> &'static str {
+        "NVIDIA website"
+    }
+
+    async fn release(&self) -> Result<DriverRelease, Box<dyn Error>> {
+        let query = &self.0;
+        super::get_latest_driver(
+            query.gpu.clone(),
+            query.driver.clone(),
+            &query.target,
+            &query.support,
+        )
+        .await
+    }
+}
+
+/// The JSON driver lookup used by GeForce Experience
+pub struct AjaxDriverService {
+    pub query: DriverQuery,
+    pub language_code: u16, // a Windows LCID rather than the lid, e.g. 1033 for English (US)
+}
+
+impl AjaxDriverService {
+    pub fn new(query: DriverQuery) -> AjaxDriverService {
+        AjaxDriverService {
+            query,
+            language_code: 1033,
+        }
+    }
+
+    pub fn link(&self, results: u8) -> String {
+        let query = &self.query;
+        let psid = query.gpu.series;
+        let pfid = query.gpu.id;
+        let osid = query.target.os;
+        let language = self.language_code;
+        let dch = query.driver.edition.into_api();
+        let crd = (query.driver.channel == DriverChannels::Studio) as u8; // Studio drivers are "Creator Ready Drivers"
+        format!("{AJAX_DRIVER_SERVICE}?func=DriverManualLookup&psid={psid}&pfid={pfid}&osID={osid}&languageCode={language}&beta=0&isWHQL=1&dltype=-1&dch={dch}&upCRD={crd}&qnf=0&sort1=0&numberOfResults={results}")
+    }
+
+    /// The newest `count` releases for the query, newest first
+    pub async fn releases(&self, count: u8) -> Result<Vec<DriverRelease>, Box<dyn Error>> {
+        let json = reqwest::get(self.link(count)).await?.text().await?;
+        AjaxDriverService::parse(&json)
+    }
+
+    /// Parses a response with any number of results, newest first
+    pub fn parse(json: &str) -> Result<Vec<DriverRelease>, Box<dyn Error>> {
+        let response: AjaxResponse = serde_json::from_str(json)?;
+        if response.success != "1" {
+            return Err("No driver found for this product".into());
+        }
+        response
+            .ids
+            .into_iter()
+            .map(|id| id.download_info.into_release())
+            .collect()
+    }
+}
+
+#[async_trait(?Send)]
+impl DriverSource for AjaxDriverService {
+    fn name(&self) -> &'static str {
+        "AjaxDriverService"
+    }
+
+    async fn release(&self) -> Result<DriverRelease, Box<dyn Error>> {
+        let release = self
+            .releases(1)
+            .await?
+            .into_iter()
+            .next()
+            .ok_or("No driver found for this product")?;
+        self.query.support.check(&release.version)?;
+        Ok(release)
+    }
+}
+
+/// Tries each source in order until one finds a release
+pub struct Fallback {
+    pub sources: Vec<Box<dyn DriverSource>>,
+    failures: RefCell<Vec<String>>, // of the last lookup, e.g. "AjaxDriverService: ..."
+}
+
+impl Fallback {
+    pub fn new(sources: Vec<Box<dyn DriverSource>>) -> Fallback {
+        Fallback {
+            sources,
+            failures: RefCell::new(Vec::new()),
+        }
+    }
+
+    /// The JSON API first, as it's less likely to break than the website.
+    /// It takes an LCID instead of the lid, so other languages than English (US) go to the website only.
+    pub fn nvidia(query: DriverQuery) -> Fallback {
+        let mut sources: Vec<Box<dyn DriverSource>> = Vec::new();
+        if query.target.language == LookupTarget::default().language {
+            sources.push(Box::new(AjaxDriverService::new(query.clone())));
+        }
+        sources.push(Box::new(NvidiaWeb(query)));
+        Fallback::new(sources)
+    }
+
+    /// Sources that failed during the last lookup, even if a later one found a release
+    pub fn failures(&self) -> Vec<String> {
+        self.failures.borrow().clone()
+    }
+}
+
+#[async_trait(?Send)]
+impl DriverSource for Fallback {
+    fn name(&self) -> &'static str {
+        "fallback"
+    }
+
+    async fn release(&self) -> Result<DriverRelease, Box<dyn Error>> {
+        self.failures.borrow_mut().clear();
+        for source in &self.sources {
+            match source.release().await {
+                Ok(release) => return Ok(release),
+                Err(e) => self
+                    .failures
+                    .borrow_mut()
+                    .push(format!("{}: {e}", source.name())),
+            }
+        }
+        Err(format!("No source found a driver ({})", self.failures().join(", ")).into())
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct AjaxResponse {
+    #[serde(rename = "Success")]
+    success: String,
+    #[serde(rename = "IDS", default)]
+    ids: Vec<AjaxId>,
+}
+
+#[derive(Debug, Deserialize)]
+struct AjaxId {
+    #[serde(rename = "downloadInfo")]
+    download_info: AjaxDownloadInfo,
+}
+
+// Most text fields are percent encoded
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct AjaxDownloadInfo {
+    version: String,           // e.g. "516.94"
+    release_date_time: String, // e.g. "Tue Aug 09, 2022"
+    #[serde(rename = "DownloadURL")]
+    download_url: String,
+    #[serde(rename = "DownloadURLFileSize")]
+    download_url_file_size: String, // e.g. "815.98 MB"
+    #[serde(default)]
+    os_name: String, // e.g. "Windows%2010%2064-bit"
+    #[serde(default)]
+    release_notes: String, // html
+    #[serde(rename = "IsCRD", default)]
+    is_crd: String, // "1" for Studio drivers
+}
+
+impl AjaxDownloadInfo {
+    fn into_release(self) -> Result<DriverRelease, Box<dyn Error>> {
+        let version: DriverVersion = self.version.parse()?;
+        let channel = match self.is_crd == "1" || self.download_url.contains("-nsd") {
+            true => DriverChannels::Studio,
+            false => DriverChannels::GameReady,
+        };
+        let text = |value: &str| Some(percent_decode(value)).filter(|value| !value.is_empty());
+        Ok(DriverRelease {
+            version,
+            release_date: text(&self.release_date_time),
+            file_size: text(&self.download_url_file_size),
+            channel,
+            os: text(&self.os_name),
+            highlights: html_to_text(&percent_decode(&self.release_notes)),
+            supported_products: Vec::new(), // not part of the response
+            download_url: self.download_url,
+        })
+    }
+}
+
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded: Vec<u8> = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
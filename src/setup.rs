@@ -1,119 +1,377 @@
 //! Anything related to the setup of the driver, inlcuding stripping and tweaking.
 
-use once_cell::sync::Lazy;
-use std::path::PathBuf;
-
-static COMPONENTS: Lazy<Vec<Component>> = Lazy::new(|| {
-    let mut comps: Vec<Component> = Vec::new();
-
-    // Telemetry
-    let comp = Component {
-        name: "Telemetry".to_string(),
-        paths: vec![
-            PathBuf::from(format!("{}\\NvTelemetry", crate::TMP_EXTRACT_DIR.display())),
-            PathBuf::from(format!(
-                "{}\\NvModuleTracker",
-                crate::TMP_EXTRACT_DIR.display()
-            )), // May be required by GFE
-        ],
-        remove: None,
-    };
-    comps.push(comp);
-
-    // GeForce Experience
-    let comp = Component {
-        name: "GeForce Experience".to_string(),
-        paths: vec![
-            PathBuf::from(format!(
-                "{}\\GFExperience",
-                crate::TMP_EXTRACT_DIR.display()
-            )),
-            PathBuf::from(format!(
-                "{}\\GFExperience.NvStreamSrvi",
-                crate::TMP_EXTRACT_DIR.display()
-            )),
-            PathBuf::from(format!("{}\\ShadowPlay", crate::TMP_EXTRACT_DIR.display())), // Perhaps we can split this in the future? Check if it depends on GFE
-            PathBuf::from(format!(
-                "{}\\ShieldWirelessController",
-                crate::TMP_EXTRACT_DIR.display()
-            )), // Perhaps we can split this in the future? Check if it depends on GFE
-        ],
-        remove: None,
-    };
-    comps.push(comp);
-
-    // Update System
-    let comp = Component {
-        name: "Update System".to_string(),
-        paths: vec![
-            PathBuf::from(format!(
-                "{}\\Display.Update",
-                crate::TMP_EXTRACT_DIR.display()
-            )), // TODO: Figure out exactly what this is
-            PathBuf::from(format!("{}\\Update.Core", crate::TMP_EXTRACT_DIR.display())),
-        ],
-        remove: None,
-    };
-    comps.push(comp);
-
-    // FrameView <https://www.nvidia.com/en-us/geforce/news/nvidia-frameview-power-and-performance-benchmarking-app-download>
-    let comp = Component {
-        name: "FrameView".to_string(),
-        paths: vec![
-            PathBuf::from(format!(
-                "{}\\Display.Update",
-                crate::TMP_EXTRACT_DIR.display()
-            )), // TODO: Figure out exactly what this is
-            PathBuf::from(format!("{}\\Update.Core", crate::TMP_EXTRACT_DIR.display())),
-        ],
-        remove: None,
-    };
-    comps.push(comp);
-
-    // Optimus <https://www.nvidia.com/en-us/geforce/technologies/optimus/technology>
-    let comp = Component {
-        name: "Optimus".to_string(),
-        paths: vec![
-            PathBuf::from(format!(
-                "{}\\Display.Optimus",
-                crate::TMP_EXTRACT_DIR.display()
-            )), // TODO: Figure out exactly what this is
-        ],
-        remove: None,
-    };
-    comps.push(comp);
+use std::{
+    error::Error,
+    path::{Path, PathBuf},
+};
 
-    comps
-});
+use catalog::Catalog;
+use graph::{DependencyGraph, StripCheck, UnsafeStrip};
+use package::Package;
+use patch::{Patch, PatchRules};
+use quarantine::Quarantine;
 
+pub mod catalog;
+pub mod graph;
+pub mod locale;
+pub mod package;
+pub mod patch;
+pub mod quarantine;
+
+/// A component of an extracted driver, see [`catalog::Catalog::components`]
 pub struct Component {
+    pub id: String,
+    pub name: String,
+    pub paths: Vec<PathBuf>,
+}
+
+pub async fn setup(root: &Path) -> Result<(), Box<dyn Error>> {
+    // Run the extracted (and possibly stripped) setup, so it doesn't matter where the installer came from
+    let status = std::process::Command::new(root.join("setup.exe")).status()?;
+    match status.success() {
+        true => Ok(()),
+        false => Err(format!("Setup failed ({status})").into()),
+    }
+}
+
+/// How `strip` treats a selection
+#[derive(Debug, Clone, Default)]
+pub struct StripOptions {
+    pub force: bool,     // strip even if kept components depend on removed ones
+    pub dry_run: bool,   // only compute the report, nothing on disk changes
+    pub permanent: bool, // delete the components instead of moving them to the quarantine
+    pub keep_locales: Option<Vec<String>>, // prune translations except these (and English), e.g. "de-DE"
+}
+
+/// Id of the translations in a [`StripReport`] and the quarantine
+pub const LOCALES_ID: &str = "locales";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ComponentStatus {
+    Removed,
+    Kept,
+    Missing,       // selected, but none of its paths are in the package
+    NotApplicable, // selected, but not for this driver version
+    Failed(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct ComponentReport {
+    pub id: String,
     pub name: String,
-    pub paths: Vec<PathBuf>, // `String` might not be the best idea, subject to change (likely PathBuf)
-    pub remove: Option<bool>,
+    pub status: ComponentStatus,
+    pub bytes: u64, // freed, or that would be freed in a dry run
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct StripReport {
+    pub dry_run: bool,
+    pub components: Vec<ComponentReport>,
+    pub check: StripCheck,
+    pub patches: Vec<Patch>, // applied to setup.cfg and the manifests, unless it's a dry run
+    pub warnings: Vec<String>, // e.g. custom components matching nothing, or conflicts ignored with `force`
+}
+
+impl StripReport {
+    pub fn bytes_freed(&self) -> u64 {
+        self.components
+            .iter()
+            .map(|component| component.bytes)
+            .sum()
+    }
+
+    pub fn has_failures(&self) -> bool {
+        self.components
+            .iter()
+            .any(|component| matches!(component.status, ComponentStatus::Failed(_)))
+    }
+}
+
+impl std::fmt::Display for StripReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for warning in &self.warnings {
+            writeln!(f, "Warning: {warning}")?;
+        }
+        for component in &self.components {
+            let status = match &component.status {
+                ComponentStatus::Removed if self.dry_run => "would be removed".to_string(),
+                ComponentStatus::Removed => "removed".to_string(),
+                ComponentStatus::Kept => "kept".to_string(),
+                ComponentStatus::Missing => "not in package".to_string(),
+                ComponentStatus::NotApplicable => "not applicable".to_string(),
+                ComponentStatus::Failed(e) => format!("failed: {e}"),
+            };
+            writeln!(
+                f,
+                "{:<24} {:>10}  {status}",
+                component.name,
+                format_size(component.bytes)
+            )?;
+        }
+        let freed = format_size(self.bytes_freed());
+        match self.dry_run {
+            true => writeln!(f, "{freed} would be freed"),
+            false => writeln!(f, "{freed} freed"),
+        }
+    }
+}
+
+pub fn format_size(bytes: u64) -> String {
+    format!("{:.2} MB", bytes as f64 / 1024.0 / 1024.0)
+}
+
+/// Size of a file, or of everything in a folder
+pub fn disk_size(path: &Path) -> std::io::Result<u64> {
+    let metadata = std::fs::symlink_metadata(path)?;
+    if !metadata.is_dir() {
+        return Ok(metadata.len());
+    }
+    let mut size = 0;
+    for entry in std::fs::read_dir(path)? {
+        size += disk_size(&entry?.path())?;
+    }
+    Ok(size)
 }
 
-pub async fn setup() {
-    let command = std::process::Command::new(crate::TMP_FILE.as_path());
+pub(crate) fn remove_path(path: &Path) -> std::io::Result<()> {
+    match path.is_dir() {
+        true => std::fs::remove_dir_all(path),
+        false => std::fs::remove_file(path),
+    }
 }
 
-pub async fn strip(components: Vec<Component>) -> std::io::Result<()> {
-    for component in components {
-        if component.remove == Some(false) {
-            break;
+/// Catalog components applying to the package, plus a component for each selected package component (e.g. "PhysX").
+/// Selected catalog components for other driver versions are skipped.
+pub(crate) fn selected_components(
+    package: &Package,
+    catalog: &Catalog,
+    selection: &[String],
+) -> Result<Vec<Component>, Box<dyn Error>> {
+    let version = package.version.as_deref().and_then(|v| v.parse().ok());
+    let mut components = catalog.components(&package.root, version);
+    for id in selection {
+        if components.iter().any(|component| &component.id == id) || catalog.get(id).is_some() {
+            continue;
         }
-        component.paths.iter().for_each(|path| {
-            if path.exists() {
-                match path.is_dir() {
-                    true => {
-                        std::fs::remove_dir_all(&path).unwrap(); // Should only error out if we don't have permissions for deletion, all other cases are covered.
-                    }
-                    false => {
-                        std::fs::remove_file(&path).unwrap();
-                    }
+        match package.get(id) {
+            Some(component) => components.push(Component {
+                id: component.id.clone(),
+                name: component.name.clone(),
+                paths: vec![package.root.join(&component.folder)],
+            }),
+            None => return Err(format!("Unknown component \"{id}\"").into()),
+        }
+    }
+    Ok(components)
+}
+
+/// Moves the paths of a component to the quarantine, deletes them if there is none, or only measures them in a dry run
+fn remove_component(
+    component: &Component,
+    package: &Package,
+    dry_run: bool,
+    quarantine: &mut Option<Quarantine>,
+) -> ComponentReport {
+    let mut entry = ComponentReport {
+        id: component.id.clone(),
+        name: component.name.clone(),
+        status: ComponentStatus::Missing,
+        bytes: 0,
+    };
+    for path in component.paths.iter().filter(|path| path.exists()) {
+        let packages: Vec<String> = match path.strip_prefix(&package.root) {
+            Ok(relative) => package.in_folder(relative).map(|c| c.id.clone()).collect(),
+            Err(_) => Vec::new(),
+        };
+        let result = disk_size(path)
+            .map_err(Box::<dyn Error>::from)
+            .and_then(|size| match (dry_run, &mut *quarantine) {
+                (true, _) => Ok(size),
+                (false, Some(quarantine)) => quarantine
+                    .store(&component.id, &component.name, path, &packages, size)
+                    .map(|_| size),
+                (false, None) => remove_path(path).map(|_| size).map_err(Into::into),
+            });
+        match result {
+            Ok(size) => {
+                entry.bytes += size;
+                if entry.status == ComponentStatus::Missing {
+                    entry.status = ComponentStatus::Removed;
                 }
             }
-        });
+            Err(e) => entry.status = ComponentStatus::Failed(format!("{}: {e}", path.display())),
+        }
+    }
+    entry
+}
+
+/// Removes the selected catalog or package components from an extracted package and patches its setup.cfg.
+/// Refuses if kept components depend on removed ones, unless forced.
+/// Removed components go to the [`Quarantine`] unless `permanent` is set.
+/// With `keep_locales`, translations to other languages are removed too and reported as [`LOCALES_ID`].
+pub async fn strip(
+    package: &Package,
+    catalog: &Catalog,
+    selection: &[String],
+    options: StripOptions,
+) -> Result<StripReport, Box<dyn Error>> {
+    let version = package.version.as_deref().and_then(|v| v.parse().ok());
+    let mut warnings = catalog.validate(&package.root, version, selection)?;
+    let components = selected_components(package, catalog, selection)?;
+    let graph = DependencyGraph::build(package, catalog);
+    let check = graph.check(selection.iter().map(String::as_str));
+    if !check.is_safe() {
+        match options.force {
+            true => warnings.extend(check.to_string().lines().map(str::to_string)),
+            false => return Err(UnsafeStrip(check).into()),
+        }
+    }
+
+    // Translations of the kept components, the removed ones go as a whole
+    let pruned = match &options.keep_locales {
+        Some(keep) => locale::removed_locales(keep)?,
+        None => Vec::new(),
+    };
+    let removed_paths: Vec<&PathBuf> = components
+        .iter()
+        .filter(|component| selection.contains(&component.id))
+        .flat_map(|component| &component.paths)
+        .collect();
+    let localized: Vec<PathBuf> = match pruned.is_empty() {
+        true => Vec::new(),
+        false => locale::find_localized(&package.root, &pruned)?
+            .into_iter()
+            .filter(|path| {
+                !removed_paths
+                    .iter()
+                    .any(|removed| path.starts_with(removed))
+            })
+            .collect(),
+    };
+
+    // Checked before anything is removed, the paths are gone afterwards
+    let removed_relative: Vec<(&str, &Path, bool)> = components
+        .iter()
+        .filter(|component| selection.contains(&component.id))
+        .flat_map(|component| {
+            component
+                .paths
+                .iter()
+                .map(|path| (component.id.as_str(), path))
+        })
+        .filter(|(_, path)| path.exists())
+        .chain(localized.iter().map(|path| (LOCALES_ID, path)))
+        .filter_map(|(id, path)| Some((id, path.strip_prefix(&package.root).ok()?, path.is_dir())))
+        .collect();
+
+    let mut quarantine = match options.dry_run || options.permanent {
+        true => None,
+        false => {
+            let mut quarantine = Quarantine::open(&package.root)?;
+            quarantine.for_version(package.version.as_deref())?;
+            Some(quarantine)
+        }
+    };
+    let mut report = StripReport {
+        dry_run: options.dry_run,
+        check,
+        warnings,
+        ..Default::default()
+    };
+    for component in &components {
+        match selection.contains(&component.id) {
+            true => report.components.push(remove_component(
+                component,
+                package,
+                options.dry_run,
+                &mut quarantine,
+            )),
+            false => report.components.push(ComponentReport {
+                id: component.id.clone(),
+                name: component.name.clone(),
+                status: ComponentStatus::Kept,
+                bytes: 0,
+            }),
+        }
+    }
+    for id in selection {
+        match catalog.get(id) {
+            Some(component) if !component.applies_to(version) => {
+                report.components.push(ComponentReport {
+                    id: component.id.clone(),
+                    name: component.name.clone(),
+                    status: ComponentStatus::NotApplicable,
+                    bytes: 0,
+                })
+            }
+            _ => {}
+        }
+    }
+    if options.keep_locales.is_some() {
+        let languages = Component {
+            id: LOCALES_ID.to_string(),
+            name: "Languages".to_string(),
+            paths: localized.clone(),
+        };
+        report.components.push(remove_component(
+            &languages,
+            package,
+            options.dry_run,
+            &mut quarantine,
+        ));
+    }
+
+    // Components that failed to go stay in setup.cfg and the manifests
+    let failed: Vec<&str> = report
+        .components
+        .iter()
+        .filter(|component| matches!(component.status, ComponentStatus::Failed(_)))
+        .map(|component| component.id.as_str())
+        .collect();
+    let failed_paths: Vec<&PathBuf> = components
+        .iter()
+        .filter(|component| failed.contains(&component.id.as_str()))
+        .flat_map(|component| &component.paths)
+        .collect();
+    let removed = report
+        .check
+        .removed
+        .iter()
+        .filter(|id| !failed.contains(&id.as_str()))
+        .filter(|id| match package.get(id) {
+            Some(component) => {
+                let folder = package.root.join(&component.folder);
+                !failed_paths.iter().any(|path| folder.starts_with(path))
+            }
+            None => true,
+        })
+        .cloned()
+        .collect();
+    let mut rules = PatchRules::for_removed(package, &removed);
+    if !failed.contains(&LOCALES_ID) {
+        rules
+            .locales
+            .extend(pruned.iter().map(|locale| locale.to_string()));
     }
+    for (_, path, is_dir) in removed_relative
+        .iter()
+        .filter(|(id, ..)| !failed.contains(id))
+    {
+        match is_dir {
+            true => rules.add_folder(path),
+            false => rules.add_file(path),
+        }
+    }
+    let patches = patch::patch_package(package, &rules)?;
 
-    Ok(())
+    if let Some(quarantine) = &mut quarantine {
+        quarantine.save_originals(&patches)?;
+    }
+    if !options.dry_run {
+        for patch in &patches {
+            patch.apply()?;
+        }
+    }
+    report.patches = patches;
+    Ok(report)
 }
@@ -0,0 +1,357 @@
+//! The catalog of strippable components, loaded from `data/components.toml` and an optional user file.
+
+use std::{
+    error::Error,
+    path::{Path, PathBuf},
+};
+
+use serde::Deserialize;
+
+use super::Component;
+use crate::nvapi::DriverVersion;
+
+/// Newest catalog format this build understands
+const CATALOG_VERSION: u32 = 1;
+const BUILTIN: &str = include_str!("../../data/components.toml");
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Catalog {
+    pub version: u32,
+    #[serde(default, rename = "component")]
+    pub components: Vec<ComponentDef>,
+    #[serde(default, rename = "profile")]
+    pub profiles: Vec<Profile>,
+}
+
+/// A named strip selection, every component that is safe to remove goes except the ones in `keep`
+#[derive(Debug, Clone, Deserialize)]
+pub struct Profile {
+    pub id: String,   // e.g. "gaming"
+    pub name: String, // e.g. "Gaming"
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub keep: Vec<String>, // catalog ids
+}
+
+impl Profile {
+    /// Ids of the catalog components the profile removes for a driver version
+    pub fn selection(&self, catalog: &Catalog, version: Option<DriverVersion>) -> Vec<String> {
+        catalog
+            .components
+            .iter()
+            .filter(|component| component.applies_to(version) && component.safe_to_remove)
+            .filter(|component| !self.keep.contains(&component.id))
+            .map(|component| component.id.clone())
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ComponentDef {
+    pub id: String,   // e.g. "gfe"
+    pub name: String, // e.g. "GeForce Experience"
+    #[serde(default)]
+    pub description: String, // what the user loses by removing it
+    #[serde(default)]
+    pub paths: Vec<String>, // globs relative to the extract directory, e.g. "GFExperience*"
+    #[serde(default)]
+    pub files: Vec<String>, // globs of single files to remove from kept folders, e.g. "Display.Driver/*.pdb"
+    #[serde(default)]
+    pub safe_to_remove: bool,
+    #[serde(default)]
+    pub requires: Vec<String>, // ids of catalog or package components this one needs
+    #[serde(default)]
+    pub min_version: Option<DriverVersion>,
+    #[serde(default)]
+    pub max_version: Option<DriverVersion>,
+    #[serde(default, rename = "mapping")]
+    pub mappings: Vec<PathMapping>, // folders only some driver versions use
+    #[serde(skip)]
+    pub builtin: bool, // shipped with NVIX rather than from the user's catalog or a preset
+}
+
+/// `[[component.mapping]]`, extra paths of a component for a range of driver versions
+#[derive(Debug, Clone, Deserialize)]
+pub struct PathMapping {
+    pub paths: Vec<String>,
+    #[serde(default)]
+    pub min_version: Option<DriverVersion>,
+    #[serde(default)]
+    pub max_version: Option<DriverVersion>,
+}
+
+/// Whether a version is in a range, anything is if the version is unknown
+fn in_range(
+    version: Option<DriverVersion>,
+    min: Option<DriverVersion>,
+    max: Option<DriverVersion>,
+) -> bool {
+    let version = match version {
+        Some(version) => version,
+        None => return true,
+    };
+    min.is_none_or(|min| version >= min) && max.is_none_or(|max| version <= max)
+}
+
+/// Paths matching a pattern relative to `root`, which may contain glob characters itself
+fn glob_in(root: &Path, pattern: &str) -> Vec<PathBuf> {
+    let pattern = format!(
+        "{}/{pattern}",
+        glob::Pattern::escape(&root.to_string_lossy())
+    );
+    glob::glob(&pattern)
+        .map(|paths| paths.filter_map(Result::ok).collect())
+        .unwrap_or_default()
+}
+
+impl ComponentDef {
+    /// Whether the component applies to a driver version, every component applies if the version is unknown
+    pub fn applies_to(&self, version: Option<DriverVersion>) -> bool {
+        in_range(version, self.min_version, self.max_version)
+    }
+
+    /// The paths with those of the mappings for the version, or of all mappings if it's unknown
+    pub fn paths_for(&self, version: Option<DriverVersion>) -> Vec<&str> {
+        let mappings = self
+            .mappings
+            .iter()
+            .filter(|mapping| in_range(version, mapping.min_version, mapping.max_version));
+        self.paths
+            .iter()
+            .chain(mappings.flat_map(|mapping| &mapping.paths))
+            .map(String::as_str)
+            .collect()
+    }
+
+    /// Whether a package folder (relative to the extract directory) belongs to this component
+    pub fn covers(&self, folder: &Path) -> bool {
+        self.paths_for(None).iter().any(|pattern| {
+            let pattern = glob::Pattern::new(pattern);
+            folder.ancestors().any(|folder| match &pattern {
+                Ok(pattern) => pattern.matches_path(folder),
+                Err(_) => false,
+            })
+        })
+    }
+
+    /// Errors on patterns that could point outside of `root`, returns warnings for the ones matching nothing
+    fn check(&self, root: &Path, version: Option<DriverVersion>) -> Result<Vec<String>, String> {
+        if self.paths.is_empty() && self.files.is_empty() {
+            return Err(format!("Component \"{}\" has no paths or files", self.id));
+        }
+        let mut warnings = Vec::new();
+        let patterns = self.paths_for(version);
+        for pattern in patterns
+            .iter()
+            .copied()
+            .chain(self.files.iter().map(String::as_str))
+        {
+            let path = Path::new(pattern);
+            if path.has_root()
+                || path.components().any(|c| {
+                    matches!(
+                        c,
+                        std::path::Component::ParentDir | std::path::Component::Prefix(_)
+                    )
+                })
+            {
+                return Err(format!(
+                    "Component \"{}\": \"{pattern}\" is outside of the driver",
+                    self.id
+                ));
+            }
+            glob::Pattern::new(pattern)
+                .map_err(|e| format!("Component \"{}\": \"{pattern}\" {e}", self.id))?;
+            if !self.builtin && glob_in(root, pattern).is_empty() {
+                warnings.push(format!(
+                    "Component \"{}\": \"{pattern}\" matches nothing",
+                    self.id
+                ));
+            }
+        }
+        Ok(warnings)
+    }
+
+    /// Expands the path globs of a driver version in `root`. Literal paths are kept even if they don't exist.
+    pub fn resolve(&self, root: &Path, version: Option<DriverVersion>) -> Component {
+        let mut paths: Vec<PathBuf> = Vec::new();
+        for pattern in self.paths_for(version) {
+            let matched = glob_in(root, pattern);
+            match matched.is_empty() && !pattern.contains(['*', '?', '[']) {
+                true => paths.push(root.join(pattern)),
+                false => paths.extend(matched),
+            }
+        }
+        for pattern in &self.files {
+            paths.extend(
+                glob_in(root, pattern)
+                    .into_iter()
+                    .filter(|path| path.is_file()),
+            );
+        }
+        // Patterns can overlap, e.g. "NvApp" and "NvApp*"
+        paths.sort();
+        paths.dedup();
+
+        Component {
+            id: self.id.clone(),
+            name: self.name.clone(),
+            paths,
+        }
+    }
+}
+
+impl Catalog {
+    pub fn parse(toml: &str) -> Result<Catalog, Box<dyn Error>> {
+        let catalog: Catalog = toml::from_str(toml)?;
+        if catalog.version > CATALOG_VERSION {
+            return Err(format!(
+                "Catalog version {} is newer than supported ({CATALOG_VERSION})",
+                catalog.version
+            )
+            .into());
+        }
+        Ok(catalog)
+    }
+
+    pub fn load(path: &Path) -> Result<Catalog, Box<dyn Error>> {
+        Catalog::parse(&std::fs::read_to_string(path)?)
+            .map_err(|e| format!("{}: {e}", path.display()).into())
+    }
+
+    /// The catalog shipped with NVIX
+    pub fn builtin() -> Catalog {
+        let mut catalog = Catalog::parse(BUILTIN).expect("built-in catalog is valid");
+        catalog
+            .check_profiles()
+            .expect("built-in profiles keep known components");
+        for component in &mut catalog.components {
+            component.builtin = true;
+        }
+        catalog
+    }
+
+    /// `%APPDATA%\NVIX\components.toml` on Windows, `~/.config/nvix/components.toml` elsewhere
+    pub fn user_path() -> Option<PathBuf> {
+        if let Some(appdata) = std::env::var_os("APPDATA") {
+            return Some(PathBuf::from(appdata).join("NVIX").join("components.toml"));
+        }
+        let config = std::env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+        Some(config.join("nvix").join("components.toml"))
+    }
+
+    /// The built-in catalog with the user's file (or `path`, if given) merged in
+    pub fn load_default(path: Option<&Path>) -> Result<Catalog, Box<dyn Error>> {
+        let mut catalog = Catalog::builtin();
+        let user = path.map(Path::to_path_buf).or_else(Catalog::user_path);
+        match user {
+            Some(user) if path.is_some() || user.exists() => {
+                catalog
+                    .merge(Catalog::load(&user)?)
+                    .map_err(|e| format!("{}: {e}", user.display()))?
+            }
+            _ => {}
+        }
+        Ok(catalog)
+    }
+
+    /// The default catalog with presets merged in, in order
+    pub fn load_with(presets: &[PathBuf]) -> Result<Catalog, Box<dyn Error>> {
+        let mut catalog = Catalog::load_default(None)?;
+        for preset in presets {
+            catalog
+                .merge(Catalog::load(preset)?)
+                .map_err(|e| format!("{}: {e}", preset.display()))?;
+        }
+        Ok(catalog)
+    }
+
+    /// Checks the patterns of the components for a driver extracted to `root`.
+    /// Patterns that could point outside of it are an error for selected components and a warning for the others,
+    /// the ones of user components that match nothing a warning.
+    pub fn validate(
+        &self,
+        root: &Path,
+        version: Option<DriverVersion>,
+        selection: &[String],
+    ) -> Result<Vec<String>, Box<dyn Error>> {
+        let mut warnings = Vec::new();
+        for component in self.components.iter().filter(|c| c.applies_to(version)) {
+            match component.check(root, version) {
+                Ok(unmatched) => warnings.extend(unmatched),
+                Err(e) if selection.contains(&component.id) => return Err(e.into()),
+                Err(e) => warnings.push(e),
+            }
+        }
+        Ok(warnings)
+    }
+
+    /// Profiles keeping components that aren't in the catalog are an error, they would remove them instead
+    fn check_profiles(&self) -> Result<(), Box<dyn Error>> {
+        for profile in &self.profiles {
+            if let Some(id) = profile.keep.iter().find(|id| self.get(id).is_none()) {
+                return Err(format!(
+                    "Profile \"{}\" keeps unknown component \"{id}\"",
+                    profile.id
+                )
+                .into());
+            }
+        }
+        Ok(())
+    }
+
+    /// Entries of `other` replace the ones with the same id, new ones are added
+    pub fn merge(&mut self, other: Catalog) -> Result<(), Box<dyn Error>> {
+        for component in other.components {
+            match self.components.iter_mut().find(|c| c.id == component.id) {
+                Some(existing) => *existing = component,
+                None => self.components.push(component),
+            }
+        }
+        for profile in other.profiles {
+            match self.profiles.iter_mut().find(|p| p.id == profile.id) {
+                Some(existing) => *existing = profile,
+                None => self.profiles.push(profile),
+            }
+        }
+        self.check_profiles()
+    }
+
+    pub fn profile(&self, id: &str) -> Option<&Profile> {
+        self.profiles.iter().find(|profile| profile.id == id)
+    }
+
+    /// What a selection removes and keeps, shown before stripping
+    pub fn summary(&self, selection: &[String], version: Option<DriverVersion>) -> String {
+        let names = |remove: bool| {
+            let names: Vec<&str> = self
+                .components
+                .iter()
+                .filter(|component| component.applies_to(version))
+                .filter(|component| selection.contains(&component.id) == remove)
+                .map(|component| component.name.as_str())
+                .collect();
+            match names.is_empty() {
+                true => "nothing".to_string(),
+                false => names.join(", "),
+            }
+        };
+        format!("Removing: {}\nKeeping: {}", names(true), names(false))
+    }
+
+    pub fn get(&self, id: &str) -> Option<&ComponentDef> {
+        self.components.iter().find(|component| component.id == id)
+    }
+
+    /// Components for a driver version with their paths in `root`, usually `TMP_EXTRACT_DIR`
+    pub fn components(&self, root: &Path, version: Option<DriverVersion>) -> Vec<Component> {
+        self.components
+            .iter()
+            .filter(|component| component.applies_to(version))
+            .map(|component| component.resolve(root, version))
+            .collect()
+    }
+}
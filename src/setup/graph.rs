@@ -0,0 +1,213 @@
+//! Dependencies between components, from the `.nvi` manifests and the catalog, to tell what a strip would break.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    error::Error,
+    fmt,
+};
+
+use super::{catalog::Catalog, package::Package};
+
+/// Nodes are package components (e.g. "Display.Driver") and catalog components (e.g. "gfe").
+/// Removing a catalog component removes the package components in its folders.
+#[derive(Debug, Clone, Default)]
+pub struct DependencyGraph {
+    requires: BTreeMap<String, BTreeSet<String>>,
+    members: BTreeMap<String, BTreeSet<String>>, // catalog id -> package ids
+    unsafe_ids: BTreeSet<String>,                // catalog components not marked safe_to_remove
+    names: BTreeMap<String, String>,
+}
+
+/// A kept component depending on a removed one
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Conflict {
+    pub kept: String,
+    pub removed: String,
+    pub kept_name: String, // display names, the id if there is none
+    pub removed_name: String,
+}
+
+/// "Graphics Driver (Display.Driver)", or only the id if it has no other name
+fn label(name: &str, id: &str) -> String {
+    match name == id {
+        true => id.to_string(),
+        false => format!("{name} ({id})"),
+    }
+}
+
+impl fmt::Display for Conflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} requires {}",
+            label(&self.kept_name, &self.kept),
+            label(&self.removed_name, &self.removed)
+        )
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct StripCheck {
+    pub removed: BTreeSet<String>, // the selection, with the package components it covers
+    pub pulled_in: BTreeSet<String>, // components that depend on the selection and would stop working
+    pub conflicts: Vec<Conflict>,
+    pub unsafe_removals: Vec<String>, // selected components the catalog doesn't consider safe to remove
+}
+
+impl StripCheck {
+    pub fn is_safe(&self) -> bool {
+        self.conflicts.is_empty() && self.unsafe_removals.is_empty()
+    }
+}
+
+impl fmt::Display for StripCheck {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for id in &self.unsafe_removals {
+            writeln!(f, "{id} is not safe to remove")?;
+        }
+        for conflict in &self.conflicts {
+            writeln!(f, "{conflict}")?;
+        }
+        if !self.pulled_in.is_empty() {
+            let pulled_in: Vec<&str> = self.pulled_in.iter().map(String::as_str).collect();
+            writeln!(f, "Removing these as well: {}", pulled_in.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+/// Returned by `strip` when the selection isn't safe and wasn't forced
+#[derive(Debug)]
+pub struct UnsafeStrip(pub StripCheck);
+
+impl fmt::Display for UnsafeStrip {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Refusing to strip, use force to ignore:\n{}", self.0)
+    }
+}
+
+impl Error for UnsafeStrip {}
+
+impl DependencyGraph {
+    pub fn build(package: &Package, catalog: &Catalog) -> DependencyGraph {
+        let mut graph = DependencyGraph::default();
+        for component in &package.components {
+            graph
+                .names
+                .insert(component.id.clone(), component.name.clone());
+            graph
+                .requires
+                .entry(component.id.clone())
+                .or_default()
+                .extend(component.requires().map(str::to_string));
+        }
+        for def in &catalog.components {
+            graph.names.insert(def.id.clone(), def.name.clone());
+            graph
+                .requires
+                .entry(def.id.clone())
+                .or_default()
+                .extend(def.requires.iter().cloned());
+            graph.members.insert(
+                def.id.clone(),
+                package
+                    .components
+                    .iter()
+                    .filter(|component| def.covers(&component.folder))
+                    .map(|component| component.id.clone())
+                    .collect(),
+            );
+            if !def.safe_to_remove {
+                graph.unsafe_ids.insert(def.id.clone());
+            }
+        }
+        graph
+    }
+
+    pub fn name<'a>(&'a self, id: &'a str) -> &'a str {
+        self.names.get(id).map_or(id, String::as_str)
+    }
+
+    /// The ids with the package components of catalog ids
+    fn expand<'a>(&self, ids: impl IntoIterator<Item = &'a str>) -> BTreeSet<String> {
+        let mut expanded = BTreeSet::new();
+        for id in ids {
+            expanded.insert(id.to_string());
+            if let Some(members) = self.members.get(id) {
+                expanded.extend(members.iter().cloned());
+            }
+        }
+        expanded
+    }
+
+    /// Catalog components are only in the package if it has one of their folders
+    fn is_present(&self, id: &str) -> bool {
+        self.members
+            .get(id)
+            .is_none_or(|members| !members.is_empty())
+    }
+
+    /// Whether a node is gone, a catalog component is gone once all its package components are
+    fn is_removed(&self, id: &str, removed: &BTreeSet<String>) -> bool {
+        if removed.contains(id) {
+            return true;
+        }
+        match self.members.get(id) {
+            Some(members) if !members.is_empty() => members.iter().all(|m| removed.contains(m)),
+            _ => false,
+        }
+    }
+
+    /// Everything that stops working when `ids` are removed, not including `ids` themselves
+    pub fn pulled_in<'a>(&self, ids: impl IntoIterator<Item = &'a str>) -> BTreeSet<String> {
+        let selected = self.expand(ids);
+        let mut removed = selected.clone();
+        loop {
+            let dependents: Vec<String> = self
+                .requires
+                .iter()
+                .filter(|(id, _)| self.is_present(id) && !self.is_removed(id, &removed))
+                .filter(|(_, requires)| requires.iter().any(|r| self.is_removed(r, &removed)))
+                .map(|(id, _)| id.clone())
+                .collect();
+            if dependents.is_empty() {
+                break;
+            }
+            removed.extend(self.expand(dependents.iter().map(String::as_str)));
+        }
+        removed.difference(&selected).cloned().collect()
+    }
+
+    /// Checks a selection of catalog or package ids to remove
+    pub fn check<'a>(&self, ids: impl IntoIterator<Item = &'a str> + Clone) -> StripCheck {
+        let removed = self.expand(ids.clone());
+        let mut conflicts = Vec::new();
+        for (id, requires) in &self.requires {
+            if !self.is_present(id) || self.is_removed(id, &removed) {
+                continue;
+            }
+            for required in requires {
+                if self.is_removed(required, &removed) {
+                    conflicts.push(Conflict {
+                        kept: id.clone(),
+                        removed: required.clone(),
+                        kept_name: self.name(id).to_string(),
+                        removed_name: self.name(required).to_string(),
+                    });
+                }
+            }
+        }
+
+        StripCheck {
+            unsafe_removals: ids
+                .clone()
+                .into_iter()
+                .filter(|id| self.unsafe_ids.contains(*id))
+                .map(str::to_string)
+                .collect(),
+            pulled_in: self.pulled_in(ids),
+            removed,
+            conflicts,
+        }
+    }
+}
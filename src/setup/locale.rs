@@ -0,0 +1,82 @@
+//! Pruning of translated files (EULAs, control panel and installer strings) for languages the user doesn't need.
+//! Driver folders are left alone, their INFs list every file they ship.
+//! Files and folders are matched by the locale in their name, e.g. `EULA/de-DE`, `license.de-de.txt` or `nvcpl_de-DE.dll`.
+
+use std::{
+    error::Error,
+    path::{Path, PathBuf},
+};
+
+/// Locales NVIDIA packages are translated to, lower case
+pub const LOCALES: [&str; 32] = [
+    "ar-sa", "cs-cz", "da-dk", "de-de", "el-gr", "en-gb", "en-us", "es-es", "es-mx", "fi-fi",
+    "fr-fr", "he-il", "hu-hu", "id-id", "it-it", "ja-jp", "ko-kr", "nb-no", "nl-nl", "pl-pl",
+    "pt-br", "pt-pt", "ro-ro", "ru-ru", "sk-sk", "sl-si", "sv-se", "th-th", "tr-tr", "uk-ua",
+    "zh-cn", "zh-tw",
+];
+
+/// English is the installer's fallback, it is kept no matter what
+pub const FALLBACK: &str = "en-us";
+
+/// `de_DE` and `de-DE` to `de-de`
+pub fn normalise(locale: &str) -> String {
+    locale.to_ascii_lowercase().replace('_', "-")
+}
+
+/// The locale of a single file or folder name
+pub fn locale_of_name(name: &str) -> Option<&'static str> {
+    let name = normalise(name);
+    name.split('.').find_map(|part| {
+        LOCALES
+            .iter()
+            .find(|locale| part == **locale || part.ends_with(&format!("-{locale}")))
+            .copied()
+    })
+}
+
+/// The locale of a path, from the first of its components that has one
+pub fn locale_of(path: &Path) -> Option<&'static str> {
+    path.components()
+        .find_map(|component| locale_of_name(&component.as_os_str().to_string_lossy()))
+}
+
+/// Locales to remove when keeping `keep`, errors on unknown locales
+pub fn removed_locales(keep: &[String]) -> Result<Vec<&'static str>, Box<dyn Error>> {
+    let keep: Vec<String> = keep.iter().map(|locale| normalise(locale)).collect();
+    if let Some(unknown) = keep
+        .iter()
+        .find(|locale| !LOCALES.contains(&locale.as_str()))
+    {
+        return Err(format!("Unknown locale \"{unknown}\", use one like \"en-US\"").into());
+    }
+    Ok(LOCALES
+        .iter()
+        .filter(|locale| **locale != FALLBACK && !keep.iter().any(|k| k == *locale))
+        .copied()
+        .collect())
+}
+
+/// Top level folders with translations that can go, the installer, EULAs and control panel resources.
+/// Others (e.g. `Display.Driver`) are listed in the `[SourceDisksFiles]` of an INF and have to stay complete.
+pub const PRUNED_FOLDERS: [&str; 3] = ["EULA", "NVI2", "NvCpl"];
+
+/// Files in `root` and its [`PRUNED_FOLDERS`] translated to one of `locales`. A folder is returned as a whole.
+pub fn find_localized(root: &Path, locales: &[&str]) -> std::io::Result<Vec<PathBuf>> {
+    find_in(root, locales, true)
+}
+
+fn find_in(folder: &Path, locales: &[&str], top: bool) -> std::io::Result<Vec<PathBuf>> {
+    let mut found = Vec::new();
+    for entry in std::fs::read_dir(folder)? {
+        let path = entry?.path();
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        let pruned = !top || PRUNED_FOLDERS.iter().any(|f| f.eq_ignore_ascii_case(&name));
+        match locale_of_name(&name) {
+            Some(locale) if locales.contains(&locale) => found.push(path),
+            Some(_) => {}
+            None if path.is_dir() && pruned => found.extend(find_in(&path, locales, false)?),
+            None => {}
+        }
+    }
+    Ok(found)
+}
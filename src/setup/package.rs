@@ -0,0 +1,263 @@
+//! The package model of an extracted driver, read from `setup.cfg` and the `*.nvi` manifest of each component.
+//!
+//! A manifest looks roughly like this:
+//! ```xml
+//! <nvi name="Display.Driver" title="${{title}}" version="516.59" disposition="demand" hidden="false">
+//!   <strings><string name="title" value="Graphics Driver"/></strings>
+//!   <dependencies><package type="requires" package="Display.PhysX"/></dependencies>
+//!   <phases><standard phase="createInstallerPackages"/></phases>
+//! </nvi>
+//! ```
+
+use std::{
+    collections::HashMap,
+    error::Error,
+    path::{Path, PathBuf},
+};
+
+use quick_xml::{
+    events::{BytesStart, Event},
+    Reader,
+};
+
+#[derive(Debug, Clone, Default)]
+pub struct Package {
+    pub root: PathBuf,
+    pub title: Option<String>,
+    pub version: Option<String>,
+    pub components: Vec<PackageComponent>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct PackageComponent {
+    pub id: String,        // e.g. "Display.Driver"
+    pub name: String,      // e.g. "Graphics Driver"
+    pub folder: PathBuf,   // folder of the manifest, relative to the package root
+    pub manifest: PathBuf, // e.g. "Display.Driver/DisplayDriver.nvi"
+    pub version: Option<String>,
+    pub hidden: bool,
+    pub dependencies: Vec<Dependency>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Dependency {
+    pub kind: String,    // "requires", "before" or "after"
+    pub package: String, // id of the other component
+}
+
+impl Dependency {
+    /// Whether the other component has to be installed as well, instead of just ordering the two
+    pub fn is_required(&self) -> bool {
+        self.kind == "requires"
+    }
+}
+
+/// XML element or attribute name as a string
+fn name_of(name: &[u8]) -> String {
+    String::from_utf8_lossy(name).into_owned()
+}
+
+fn attributes(
+    element: &BytesStart,
+    reader: &Reader<&[u8]>,
+) -> Result<HashMap<String, String>, Box<dyn Error>> {
+    let mut map = HashMap::new();
+    for attribute in element.attributes() {
+        let attribute = attribute?;
+        map.insert(
+            name_of(attribute.key),
+            attribute.unescape_and_decode_value(reader)?,
+        );
+    }
+    Ok(map)
+}
+
+/// Replaces `${{name}}` placeholders, unknown ones are left as is
+pub fn substitute(value: &str, properties: &HashMap<String, String>) -> String {
+    let mut out = String::with_capacity(value.len());
+    let mut rest = value;
+    while let Some(start) = rest.find("${{") {
+        out.push_str(&rest[..start]);
+        match rest[start..].find("}}") {
+            Some(end) => {
+                let name = &rest[start + 3..start + end];
+                match properties.get(name) {
+                    Some(value) => out.push_str(value),
+                    None => out.push_str(&rest[start..start + end + 2]),
+                }
+                rest = &rest[start + end + 2..];
+            }
+            None => {
+                out.push_str(&rest[start..]);
+                rest = "";
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Elements of a setup.cfg or .nvi file, shared by both as they use the same layout
+#[derive(Debug, Default)]
+struct Document {
+    root: HashMap<String, String>,
+    properties: HashMap<String, String>,
+    dependencies: Vec<Dependency>,
+}
+
+fn parse_document(xml: &str) -> Result<Document, Box<dyn Error>> {
+    let mut reader = Reader::from_str(xml);
+    reader.trim_text(true);
+    let mut buf = Vec::new();
+    let mut document = Document::default();
+    let mut path: Vec<String> = Vec::new();
+    let mut has_root = false;
+
+    loop {
+        buf.clear();
+        let (element, empty) = match reader.read_event(&mut buf)? {
+            Event::Start(element) => (element.into_owned(), false),
+            Event::Empty(element) => (element.into_owned(), true),
+            Event::End(_) => {
+                path.pop();
+                continue;
+            }
+            Event::Eof => break,
+            _ => continue,
+        };
+        let name = name_of(element.name());
+        let parent = path.last().map(String::as_str);
+        let attributes = attributes(&element, &reader)?;
+
+        match (parent, name.as_str()) {
+            (None, _) => {
+                document.root = attributes.clone();
+                has_root = true;
+            }
+            (Some("properties" | "strings"), _) => {
+                if let (Some(key), Some(value)) = (attributes.get("name"), attributes.get("value"))
+                {
+                    document.properties.insert(key.clone(), value.clone());
+                }
+            }
+            (Some("dependencies"), "package") => {
+                if let Some(package) = attributes.get("package") {
+                    document.dependencies.push(Dependency {
+                        kind: attributes
+                            .get("type")
+                            .cloned()
+                            .unwrap_or_else(|| "requires".to_string()),
+                        package: package.clone(),
+                    });
+                }
+            }
+            _ => {}
+        }
+
+        if !empty {
+            path.push(name);
+        }
+    }
+
+    if !has_root {
+        return Err("Document has no root element".into());
+    }
+    Ok(document)
+}
+
+impl PackageComponent {
+    /// Parses a `.nvi` manifest, `manifest` is the path relative to the package root
+    pub fn parse(xml: &str, manifest: &Path) -> Result<PackageComponent, Box<dyn Error>> {
+        let document = parse_document(xml)?;
+        let attribute = |name: &str| {
+            document
+                .root
+                .get(name)
+                .map(|value| substitute(value, &document.properties))
+        };
+
+        let folder = manifest.parent().map(Path::to_path_buf).unwrap_or_default();
+        let id = attribute("name")
+            .or_else(|| folder.to_str().map(str::to_string))
+            .ok_or_else(|| format!("{} has no name", manifest.display()))?;
+        Ok(PackageComponent {
+            name: attribute("title").unwrap_or_else(|| id.clone()),
+            id,
+            folder,
+            manifest: manifest.to_path_buf(),
+            version: attribute("version"),
+            hidden: attribute("hidden").as_deref() == Some("true"),
+            dependencies: document.dependencies,
+        })
+    }
+
+    pub fn requires(&self) -> impl Iterator<Item = &str> {
+        self.dependencies
+            .iter()
+            .filter(|dependency| dependency.is_required())
+            .map(|dependency| dependency.package.as_str())
+    }
+}
+
+impl Package {
+    /// Parses the `setup.cfg` of a package, without its components
+    pub fn parse_setup(xml: &str, root: &Path) -> Result<Package, Box<dyn Error>> {
+        let document = parse_document(xml)?;
+        Ok(Package {
+            root: root.to_path_buf(),
+            title: document
+                .root
+                .get("title")
+                .map(|title| substitute(title, &document.properties)),
+            version: document
+                .root
+                .get("version")
+                .map(|version| substitute(version, &document.properties)),
+            components: Vec::new(),
+        })
+    }
+
+    /// Reads the package extracted to `root`, usually `TMP_EXTRACT_DIR`.
+    /// Manifests are searched two levels deep, e.g. `GFExperience/PrivacyPolicy/PrivacyPolicy.nvi`.
+    pub fn load(root: &Path) -> Result<Package, Box<dyn Error>> {
+        let setup = root.join("setup.cfg");
+        let mut package = match setup.exists() {
+            true => Package::parse_setup(&std::fs::read_to_string(&setup)?, root)
+                .map_err(|e| format!("{}: {e}", setup.display()))?,
+            false => {
+                return Err(
+                    format!("{} not found, was the driver extracted?", setup.display()).into(),
+                )
+            }
+        };
+
+        let pattern = format!(
+            "{}/*/**/*.nvi",
+            glob::Pattern::escape(&root.to_string_lossy())
+        );
+        for manifest in glob::glob(&pattern)?.filter_map(Result::ok) {
+            let relative = manifest.strip_prefix(root)?.to_path_buf();
+            if relative.components().count() > 3 {
+                continue;
+            }
+            let component =
+                PackageComponent::parse(&std::fs::read_to_string(&manifest)?, &relative)
+                    .map_err(|e| format!("{}: {e}", manifest.display()))?;
+            package.components.push(component);
+        }
+        package.components.sort_by(|a, b| a.id.cmp(&b.id));
+        Ok(package)
+    }
+
+    pub fn get(&self, id: &str) -> Option<&PackageComponent> {
+        self.components.iter().find(|component| component.id == id)
+    }
+
+    /// Components whose folder is `folder` or below it
+    pub fn in_folder(&self, folder: &Path) -> impl Iterator<Item = &PackageComponent> {
+        let folder = folder.to_path_buf();
+        self.components
+            .iter()
+            .filter(move |component| component.folder.starts_with(&folder))
+    }
+}
@@ -0,0 +1,260 @@
+//! Edits `setup.cfg` and the `.nvi` manifests after stripping, so the installer doesn't look for what was removed.
+//! Elements are cut out of the original text, everything else (formatting, comments, encoding) is kept as is.
+
+use std::{
+    collections::BTreeSet,
+    error::Error,
+    path::{Path, PathBuf},
+};
+
+use quick_xml::{events::Event, Reader};
+
+use super::{locale, package::Package};
+
+/// `<file name="${{...}}"/>` entries of setup.cfg that make the installer show extra pages or fail once stripped
+pub const DEFAULT_FILE_KEYS: [&str; 3] =
+    ["EulaHtmlFile", "FunctionalConsentFile", "PrivacyPolicyFile"];
+
+#[derive(Debug, Clone)]
+pub struct PatchRules {
+    pub file_keys: Vec<String>, // e.g. "EulaHtmlFile" for `<file name="${{EulaHtmlFile}}"/>`
+    pub packages: BTreeSet<String>, // removed package components, e.g. "Display.Optimus"
+    pub folders: BTreeSet<String>, // removed folders, lower case with `/`, e.g. "gfexperience/privacypolicy"
+    pub locales: BTreeSet<String>, // pruned locales, lower case, e.g. "de-de", their files are in `files` and `folders`
+    pub files: BTreeSet<String>, // removed single files, lower case with `/`, e.g. "display.driver/nvdebugdump.exe"
+}
+
+impl Default for PatchRules {
+    fn default() -> Self {
+        PatchRules {
+            file_keys: DEFAULT_FILE_KEYS
+                .iter()
+                .map(|key| key.to_string())
+                .collect(),
+            packages: BTreeSet::new(),
+            folders: BTreeSet::new(),
+            locales: BTreeSet::new(),
+            files: BTreeSet::new(),
+        }
+    }
+}
+
+impl PatchRules {
+    /// Rules for the removed package components of a package, see `StripCheck::removed`
+    pub fn for_removed(package: &Package, removed: &BTreeSet<String>) -> PatchRules {
+        let mut rules = PatchRules::default();
+        for component in package
+            .components
+            .iter()
+            .filter(|c| removed.contains(&c.id))
+        {
+            rules.packages.insert(component.id.clone());
+            rules.add_folder(&component.folder);
+        }
+        rules
+    }
+
+    /// A `<file name="..."/>` pointing to a listed key or into a removed folder
+    fn removes_file(&self, name: &str) -> bool {
+        if let Some(key) = name
+            .strip_prefix("${{")
+            .and_then(|key| key.strip_suffix("}}"))
+        {
+            return self.file_keys.iter().any(|k| k == key);
+        }
+        let name = relative_name(Path::new(name));
+        self.files.contains(&name)
+            || self
+                .folders
+                .iter()
+                .any(|folder| name.starts_with(&format!("{folder}/")))
+    }
+
+    /// An element only used for a pruned language, e.g. `<string locale="de-DE" .../>`
+    fn removes_locale(&self, locale: &str) -> bool {
+        self.locales.contains(&locale::normalise(locale))
+    }
+
+    /// Adds a removed file, `relative` to the package root
+    pub fn add_file(&mut self, relative: &Path) {
+        self.files.insert(relative_name(relative));
+    }
+
+    /// Adds a removed folder, `relative` to the package root
+    pub fn add_folder(&mut self, relative: &Path) {
+        let name = relative_name(relative);
+        if !name.is_empty() {
+            self.folders.insert(name);
+        }
+    }
+
+    /// A `<package package="..."/>` dependency on a removed package component
+    fn removes_package(&self, package: &str) -> bool {
+        self.packages
+            .iter()
+            .any(|p| p.eq_ignore_ascii_case(package))
+    }
+}
+
+/// `Display.Driver\\nvapi.pdb` or `./Display.Driver/nvapi.pdb` to `display.driver/nvapi.pdb`
+fn relative_name(path: &Path) -> String {
+    let name = path
+        .to_string_lossy()
+        .replace('\\', "/")
+        .to_ascii_lowercase();
+    name.split('/')
+        .filter(|part| !part.is_empty() && *part != ".")
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Byte range of `start..end`, widened to the whole line if the element is the only thing on it
+fn line_range(xml: &str, start: usize, end: usize) -> (usize, usize) {
+    let before = &xml[..start];
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    if !before[line_start..].trim().is_empty() {
+        return (start, end);
+    }
+    let after = &xml[end..];
+    let line_end = after.find('\n').map_or(xml.len(), |i| end + i + 1);
+    match xml[end..line_end].trim().is_empty() {
+        true => (line_start, line_end),
+        false => (start, end),
+    }
+}
+
+/// Removes the elements `rules` match from an XML document
+pub fn patch_xml(xml: &str, rules: &PatchRules) -> Result<String, Box<dyn Error>> {
+    let mut reader = Reader::from_str(xml);
+    let mut buf = Vec::new();
+    let mut skip = Vec::new();
+    let mut cuts: Vec<(usize, usize)> = Vec::new();
+
+    loop {
+        let start = reader.buffer_position();
+        buf.clear();
+        let (element, empty) = match reader.read_event(&mut buf)? {
+            Event::Start(element) => (element.into_owned(), false),
+            Event::Empty(element) => (element.into_owned(), true),
+            Event::Eof => break,
+            _ => continue,
+        };
+
+        let attribute = |name: &[u8]| -> Result<Option<String>, Box<dyn Error>> {
+            for attribute in element.attributes() {
+                let attribute = attribute?;
+                if attribute.key == name {
+                    return Ok(Some(attribute.unescape_and_decode_value(&reader)?));
+                }
+            }
+            Ok(None)
+        };
+        let mut remove = match element.name() {
+            b"file" => attribute(b"name")?.is_some_and(|name| rules.removes_file(&name)),
+            b"package" => attribute(b"package")?.is_some_and(|name| rules.removes_package(&name)),
+            _ => false,
+        };
+        for name in [&b"locale"[..], b"language", b"culture"] {
+            remove |= attribute(name)?.is_some_and(|locale| rules.removes_locale(&locale));
+        }
+        if !remove {
+            continue;
+        }
+
+        if !empty {
+            reader.read_to_end(element.name(), &mut skip)?;
+        }
+        cuts.push(line_range(xml, start, reader.buffer_position()));
+    }
+
+    let mut patched = String::with_capacity(xml.len());
+    let mut last = 0;
+    for (start, end) in cuts {
+        patched.push_str(&xml[last..start]);
+        last = end;
+    }
+    patched.push_str(&xml[last..]);
+    Ok(patched)
+}
+
+/// A changed file, nothing is written until `apply`
+#[derive(Debug, Clone)]
+pub struct Patch {
+    pub path: PathBuf,
+    pub original: String,
+    pub patched: String,
+}
+
+impl Patch {
+    pub fn is_empty(&self) -> bool {
+        self.original == self.patched
+    }
+
+    pub fn apply(&self) -> std::io::Result<()> {
+        std::fs::write(&self.path, &self.patched)
+    }
+
+    /// Line diff of the patch, removed lines start with `-` and added ones with `+`
+    pub fn diff(&self) -> String {
+        let old: Vec<&str> = self.original.lines().collect();
+        let new: Vec<&str> = self.patched.lines().collect();
+
+        // Longest common subsequence, the files are small enough for the table
+        let mut lcs = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+        for i in (0..old.len()).rev() {
+            for j in (0..new.len()).rev() {
+                lcs[i][j] = match old[i] == new[j] {
+                    true => lcs[i + 1][j + 1] + 1,
+                    false => lcs[i + 1][j].max(lcs[i][j + 1]),
+                };
+            }
+        }
+
+        let mut diff = format!("--- {0}\n+++ {0}\n", self.path.display());
+        let (mut i, mut j) = (0, 0);
+        while i < old.len() || j < new.len() {
+            if i < old.len() && j < new.len() && old[i] == new[j] {
+                i += 1;
+                j += 1;
+            } else if j < new.len() && (i == old.len() || lcs[i][j + 1] >= lcs[i + 1][j]) {
+                diff.push_str(&format!("{:>5} +{}\n", j + 1, new[j]));
+                j += 1;
+            } else {
+                diff.push_str(&format!("{:>5} -{}\n", i + 1, old[i]));
+                i += 1;
+            }
+        }
+        diff
+    }
+}
+
+fn patch_file(path: &Path, rules: &PatchRules) -> Result<Patch, Box<dyn Error>> {
+    let original = std::fs::read_to_string(path)?;
+    let patched = patch_xml(&original, rules).map_err(|e| format!("{}: {e}", path.display()))?;
+    Ok(Patch {
+        path: path.to_path_buf(),
+        original,
+        patched,
+    })
+}
+
+/// Patches for setup.cfg and the manifests of the kept components, only files that change are returned
+pub fn patch_package(package: &Package, rules: &PatchRules) -> Result<Vec<Patch>, Box<dyn Error>> {
+    let mut files = vec![package.root.join("setup.cfg")];
+    files.extend(
+        package
+            .components
+            .iter()
+            .filter(|component| !rules.packages.contains(&component.id))
+            .map(|component| package.root.join(&component.manifest)),
+    );
+
+    let mut patches = Vec::new();
+    for file in files.iter().filter(|file| file.exists()) {
+        let patch = patch_file(file, rules)?;
+        if !patch.is_empty() {
+            patches.push(patch);
+        }
+    }
+    Ok(patches)
+}
@@ -0,0 +1,253 @@
+//! Stripped components are moved next to the extracted package instead of being deleted, so they can be put back
+//! without extracting the driver again. `quarantine.json` keeps track of where everything came from.
+
+use std::{
+    error::Error,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use super::{
+    locale,
+    patch::{self, Patch, PatchRules},
+    LOCALES_ID,
+};
+
+const MANIFEST: &str = "quarantine.json";
+const ORIGINALS: &str = "originals"; // setup.cfg and manifests as they were before the first patch
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct QuarantineManifest {
+    #[serde(default)]
+    pub version: Option<String>, // of the package the entries and originals are from
+    pub entries: Vec<QuarantineEntry>,
+    pub originals: Vec<PathBuf>, // relative to the package, saved copies are in `originals`
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QuarantineEntry {
+    pub id: String,
+    pub name: String,
+    pub paths: Vec<PathBuf>, // relative to the package, stored at the same place under `{id}`
+    #[serde(default)]
+    pub files: Vec<PathBuf>, // single files taken out of kept folders, stored like `paths`
+    pub packages: Vec<String>, // package components that went with it, to patch setup.cfg again after a restore
+    pub bytes: u64,
+}
+
+#[derive(Debug)]
+pub struct Quarantine {
+    pub root: PathBuf, // the extracted package
+    pub dir: PathBuf,
+    pub manifest: QuarantineManifest,
+}
+
+impl Quarantine {
+    /// `%TEMP%\NVIX.quarantine` for `%TEMP%\NVIX`, outside the package so the installer doesn't see it
+    pub fn dir_for(root: &Path) -> PathBuf {
+        let mut name = root.file_name().unwrap_or_default().to_os_string();
+        name.push(".quarantine");
+        root.with_file_name(name)
+    }
+
+    pub fn open(root: &Path) -> Result<Quarantine, Box<dyn Error>> {
+        let dir = Quarantine::dir_for(root);
+        let manifest = match dir.join(MANIFEST).exists() {
+            true => serde_json::from_str(&std::fs::read_to_string(dir.join(MANIFEST))?)?,
+            false => QuarantineManifest::default(),
+        };
+        Ok(Quarantine {
+            root: root.to_path_buf(),
+            dir,
+            manifest,
+        })
+    }
+
+    /// Empties the quarantine if it holds files of another driver version, e.g. after extracting a newer driver to the same folder
+    pub fn for_version(&mut self, version: Option<&str>) -> Result<(), Box<dyn Error>> {
+        let empty = self.manifest.entries.is_empty() && self.manifest.originals.is_empty();
+        if !empty && self.manifest.version.as_deref() != version {
+            if self.dir.exists() {
+                std::fs::remove_dir_all(&self.dir)?;
+            }
+            self.manifest = QuarantineManifest::default();
+        }
+        self.manifest.version = version.map(str::to_string);
+        Ok(())
+    }
+
+    pub fn save(&self) -> Result<(), Box<dyn Error>> {
+        std::fs::create_dir_all(&self.dir)?;
+        std::fs::write(
+            self.dir.join(MANIFEST),
+            serde_json::to_string_pretty(&self.manifest)?,
+        )?;
+        Ok(())
+    }
+
+    pub fn entries(&self) -> &[QuarantineEntry] {
+        &self.manifest.entries
+    }
+
+    pub fn get(&self, id: &str) -> Option<&QuarantineEntry> {
+        self.manifest.entries.iter().find(|entry| entry.id == id)
+    }
+
+    fn relative(&self, path: &Path) -> Result<PathBuf, Box<dyn Error>> {
+        Ok(path
+            .strip_prefix(&self.root)
+            .map_err(|_| format!("{} is not in {}", path.display(), self.root.display()))?
+            .to_path_buf())
+    }
+
+    /// Moves `path` of a component into the quarantine and saves the manifest, so it never misses a moved file
+    pub fn store(
+        &mut self,
+        id: &str,
+        name: &str,
+        path: &Path,
+        packages: &[String],
+        bytes: u64,
+    ) -> Result<(), Box<dyn Error>> {
+        let relative = self.relative(path)?;
+        let is_file = path.is_file();
+        let stored = self.dir.join(id).join(&relative);
+        if stored.exists() {
+            // Left over from an earlier extraction of the package
+            super::remove_path(&stored)?;
+        }
+        if let Some(parent) = stored.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::rename(path, &stored)?;
+
+        let index = match self.manifest.entries.iter().position(|e| e.id == id) {
+            Some(index) => index,
+            None => {
+                self.manifest.entries.push(QuarantineEntry {
+                    id: id.to_string(),
+                    name: name.to_string(),
+                    paths: Vec::new(),
+                    files: Vec::new(),
+                    packages: Vec::new(),
+                    bytes: 0,
+                });
+                self.manifest.entries.len() - 1
+            }
+        };
+        let entry = &mut self.manifest.entries[index];
+        let paths = match is_file {
+            true => &mut entry.files,
+            false => &mut entry.paths,
+        };
+        if !paths.contains(&relative) {
+            paths.push(relative);
+        }
+        for package in packages {
+            if !entry.packages.contains(package) {
+                entry.packages.push(package.clone());
+            }
+        }
+        entry.bytes += bytes;
+        self.save()
+    }
+
+    /// Keeps a copy of the files `patches` change, unless one was kept by an earlier strip
+    pub fn save_originals(&mut self, patches: &[Patch]) -> Result<(), Box<dyn Error>> {
+        for patch in patches {
+            let relative = self.relative(&patch.path)?;
+            if self.manifest.originals.contains(&relative) {
+                continue;
+            }
+            let copy = self.dir.join(ORIGINALS).join(&relative);
+            if let Some(parent) = copy.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            std::fs::write(copy, &patch.original)?;
+            self.manifest.originals.push(relative);
+        }
+        self.save()
+    }
+
+    /// Rules to patch the package for what's still in quarantine
+    pub fn patch_rules(&self) -> PatchRules {
+        let mut rules = PatchRules::default();
+        for entry in &self.manifest.entries {
+            rules.packages.extend(entry.packages.iter().cloned());
+            for path in entry.paths.iter().chain(&entry.files) {
+                if let Some(locale) = locale::locale_of(path).filter(|_| entry.id == LOCALES_ID) {
+                    rules.locales.insert(locale.to_string());
+                }
+                if entry.files.contains(path) {
+                    rules.add_file(path);
+                    continue;
+                }
+                rules.add_folder(path);
+            }
+        }
+        rules
+    }
+
+    /// Puts components back into the package, all of them if `ids` is empty. Returns the restored ids.
+    pub fn restore(&mut self, ids: &[String]) -> Result<Vec<String>, Box<dyn Error>> {
+        for id in ids {
+            if self.get(id).is_none() {
+                return Err(format!("\"{id}\" is not in quarantine").into());
+            }
+        }
+        let (restore, keep): (Vec<QuarantineEntry>, Vec<QuarantineEntry>) = self
+            .manifest
+            .entries
+            .iter()
+            .cloned()
+            .partition(|entry| ids.is_empty() || ids.contains(&entry.id));
+        for path in restore
+            .iter()
+            .flat_map(|entry| entry.paths.iter().chain(&entry.files))
+        {
+            let target = self.root.join(path);
+            if target.exists() {
+                return Err(format!(
+                    "{} exists already, was the driver extracted again?",
+                    target.display()
+                )
+                .into());
+            }
+        }
+
+        let mut restored = Vec::new();
+        for entry in restore {
+            for path in entry.paths.iter().chain(&entry.files) {
+                let target = self.root.join(path);
+                if let Some(parent) = target.parent() {
+                    std::fs::create_dir_all(parent)?;
+                }
+                std::fs::rename(self.dir.join(&entry.id).join(path), &target)?;
+            }
+            let _ = std::fs::remove_dir_all(self.dir.join(&entry.id));
+            restored.push(entry.id);
+        }
+        self.manifest.entries = keep;
+
+        // Start over from the original files and patch them for what's left, if anything
+        let rules = self.patch_rules();
+        for relative in &self.manifest.originals {
+            let original = std::fs::read_to_string(self.dir.join(ORIGINALS).join(relative))?;
+            let target = self.root.join(relative);
+            if target.parent().is_some_and(Path::exists) {
+                let patched = match self.manifest.entries.is_empty() {
+                    true => original,
+                    false => patch::patch_xml(&original, &rules)?,
+                };
+                std::fs::write(&target, patched)?;
+            }
+        }
+        if self.manifest.entries.is_empty() {
+            self.manifest.originals.clear();
+            let _ = std::fs::remove_dir_all(self.dir.join(ORIGINALS));
+        }
+        self.save()?;
+        Ok(restored)
+    }
+}
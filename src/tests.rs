@@ -63,6 +63,1270 @@ fn test_gpu_list() {
         if gpu.name.contains("GeForce RTX 3090 Ti") {
             assert_eq!(gpu.series, 120);
             assert_eq!(gpu.id, 985);
+            assert_eq!(gpu.series_name.as_deref(), Some("GeForce RTX 30 Series"));
         }
     }
 }
+
+#[test]
+fn test_product_tree() {
+    let lookup = |values: &str| {
+        nvapi::xml::parse_lookup(&format!(
+            r#"<?xml version="1.0" encoding="utf-8"?><LookupValueSearch><LookupValues>{values}</LookupValues></LookupValueSearch>"#
+        ))
+        .unwrap()
+    };
+    let types =
+        lookup(r#"<LookupValue ParentID="1"><Name>GeForce</Name><Value>1</Value></LookupValue>"#);
+    let series = lookup(
+        r#"<LookupValue ParentID="1"><Name>GeForce RTX 30 Series</Name><Value>120</Value></LookupValue>
+        <LookupValue ParentID="1"><Name>GeForce RTX 30 Series (Notebooks)</Name><Value>123</Value></LookupValue>"#,
+    );
+    let products = lookup(
+        r#"<LookupValue ParentID="120"><Name>GeForce RTX 3070</Name><Value>933</Value></LookupValue>
+        <LookupValue ParentID="123"><Name>GeForce RTX 3070 Laptop GPU</Name><Value>939</Value></LookupValue>
+        <LookupValue ParentID="999"><Name>NVIDIA RTX A400</Name><Value>1052</Value></LookupValue>"#,
+    );
+    let tree = nvapi::xml::ProductTree::build(&types, &series, &products);
+
+    assert_eq!(tree.types[0].name, "GeForce");
+    assert_eq!(tree.products().count(), 3);
+    assert_eq!(tree.unlisted.len(), 1);
+    assert_eq!(tree.unlisted[0].series_name, None);
+    assert_eq!(
+        nvapi::xml::gpu_entries(&series, &products),
+        tree.products().cloned().collect::<Vec<_>>()
+    );
+    let series = tree.find_series(123).unwrap();
+    assert!(series.is_notebook());
+    assert_eq!(
+        series.products[0].display_name(),
+        "GeForce RTX 3070 Laptop GPU - GeForce RTX 30 Series (Notebooks)"
+    );
+}
+
+#[test]
+fn test_resolve_mobile_name() {
+    let entry = |name: &str, series: u16, id: u16| nvapi::xml::XmlGpuEntry {
+        name: name.to_string(),
+        series,
+        id,
+        series_name: None,
+    };
+    let list = vec![
+        entry("GeForce RTX 3070 Ti", 120, 964),
+        entry("GeForce RTX 3070", 120, 933),
+        entry("GeForce RTX 3070 Ti Laptop GPU", 123, 974),
+        entry("GeForce RTX 3070 Laptop GPU", 123, 939),
+    ];
+
+    let (gpu, confidence) =
+        nvapi::resolve::resolve_gpu("GeForce RTX 3070 Mobile / Max-Q", None, &list).unwrap();
+    assert_eq!(gpu.id, 939);
+    assert_eq!(confidence, nvapi::resolve::Confidence::Exact);
+
+    let (gpu, _) = nvapi::resolve::resolve_gpu("GeForce RTX 3070 Ti", None, &list).unwrap();
+    assert_eq!(gpu.id, 964);
+    assert!(nvapi::resolve::resolve_gpu("GeForce RTX 3080", None, &list).is_none());
+
+    // Pre-RTX mobile GPUs have the desktop name, only their series tells them apart
+    let series = |name: &str, series: u16, id: u16, series_name: &str| nvapi::xml::XmlGpuEntry {
+        series_name: Some(series_name.to_string()),
+        ..entry(name, series, id)
+    };
+    let list = vec![
+        series("GeForce GTX 1060", 101, 815, "GeForce 10 Series"),
+        series(
+            "GeForce GTX 1060",
+            102,
+            819,
+            "GeForce 10 Series (Notebooks)",
+        ),
+    ];
+    let (gpu, confidence) =
+        nvapi::resolve::resolve_gpu("GeForce GTX 1060 Mobile", None, &list).unwrap();
+    assert_eq!(gpu.id, 819);
+    assert_eq!(confidence, nvapi::resolve::Confidence::Exact);
+    let notebook = Some(nvapi::DriverPlatform::Notebook);
+    let (gpu, _) = nvapi::resolve::resolve_gpu("GeForce GTX 1060", notebook, &list).unwrap();
+    assert_eq!(gpu.id, 819);
+    let (gpu, _) = nvapi::resolve::resolve_gpu("GeForce GTX 1060", None, &list).unwrap();
+    assert_eq!(gpu.id, 815);
+}
+
+#[test]
+fn test_supported_gpus_lookup() {
+    let json = r#"{"chips": [
+        {"devid": "0x1180", "name": "NVIDIA GeForce GTX 680", "legacybranch": "470.xx"},
+        {"devid": "0x2204", "name": "NVIDIA GeForce RTX 3090", "features": ["kernelopen"]},
+        {"devid": "0x2204", "subdevid": "0x147D", "subvendorid": "0x10DE", "name": "NVIDIA GeForce RTX 3090 Founders"}
+    ]}"#;
+    let gpus = nvapi::devices::SupportedGpus::parse(json).unwrap();
+
+    let chip = gpus.lookup("1180", None).unwrap();
+    assert_eq!(chip.name(), "GeForce GTX 680");
+    assert_eq!(chip.legacybranch.as_deref(), Some("470.xx"));
+
+    assert_eq!(gpus.lookup("2204", None).unwrap().name(), "GeForce RTX 3090");
+    let chip = gpus.lookup("2204", Some(("10de", "147d"))).unwrap();
+    assert_eq!(chip.name(), "GeForce RTX 3090 Founders");
+    assert!(gpus.lookup("ffff", None).is_none());
+
+    let ids = nvapi::PciIds::parse("PCI\\VEN_10DE&DEV_2204&SUBSYS_147D10DE&REV_A1").unwrap();
+    assert_eq!(ids.device, "2204");
+    assert_eq!(
+        ids.subsystem,
+        Some(("10de".to_string(), "147d".to_string()))
+    );
+    assert_eq!(
+        nvapi::PciIds::parse("pci\\ven_10de&dev_1180")
+            .unwrap()
+            .subsystem,
+        None
+    );
+}
+
+#[test]
+fn test_legacy_support() {
+    use nvapi::legacy::{DriverBranch, Support};
+
+    let version: nvapi::DriverVersion = "474.44".parse().unwrap();
+    assert_eq!(version, nvapi::DriverVersion::new(474, 44));
+
+    let kepler = Support::from_name("GeForce GTX 680");
+    assert_eq!(kepler, Support::Legacy(DriverBranch(470)));
+    assert!(kepler.check(&version).is_ok());
+    assert!(kepler.check(&"516.59".parse().unwrap()).is_err());
+
+    assert_eq!(
+        Support::from_name("GeForce GTX 1080 Ti"),
+        Support::Legacy(DriverBranch(580))
+    );
+    assert_eq!(Support::from_name("GeForce RTX 3090"), Support::Current);
+    assert_eq!(
+        Support::from_name("Quadro GV100"),
+        Support::Legacy(DriverBranch(580))
+    );
+    let gpu = nvapi::DetectedGpu {
+        device_id: "0ffe".to_string(),
+        name: "NVS 510".to_string(), // not recognised by name
+        chip: None,
+        legacy_branch: Some("470.xx".to_string()),
+    };
+    assert_eq!(Support::from_name(&gpu.name), Support::Current);
+    assert_eq!(Support::of(&gpu), Support::Legacy(DriverBranch(470)));
+    assert_eq!("470.xx".parse::<DriverBranch>().unwrap(), DriverBranch(470));
+}
+
+#[test]
+fn test_platform_inference() {
+    let gpu = |name: &str, chip: Option<&str>| nvapi::DetectedGpu {
+        device_id: String::new(),
+        name: name.to_string(),
+        chip: chip.map(str::to_string),
+        legacy_branch: None,
+    };
+    use nvapi::DriverPlatform::{Desktop, Notebook};
+
+    let mobile = gpu("GeForce RTX 3070 Mobile / Max-Q", Some("GA104M"));
+    assert_eq!(nvapi::DriverPlatform::infer(&mobile, None), Notebook);
+    let mx = gpu("GeForce MX450", Some("TU117"));
+    assert_eq!(nvapi::DriverPlatform::infer(&mx, None), Notebook);
+    let desktop = gpu("GeForce RTX 3070", Some("GA104"));
+    assert_eq!(nvapi::DriverPlatform::infer(&desktop, None), Desktop);
+
+    let driver = nvapi::Driver::detected("516.59".to_string(), &mobile, None, Some(Desktop));
+    assert_eq!(driver.platform, Desktop);
+}
+
+#[test]
+fn test_os_lookup() {
+    let values = nvapi::xml::parse_lookup(
+        r#"<?xml version="1.0" encoding="utf-8"?><LookupValueSearch><LookupValues>
+        <LookupValue Code="10.0" ParentID="1"><Name>Windows 10 64-bit</Name><Value>57</Value></LookupValue>
+        <LookupValue Code="10.0" ParentID="1"><Name>Windows 11</Name><Value>135</Value></LookupValue>
+        <LookupValue Code="Linux" ParentID="1"><Name>Linux 64-bit</Name><Value>12</Value></LookupValue>
+        </LookupValues></LookupValueSearch>"#,
+    )
+    .unwrap();
+    let list: Vec<nvapi::xml::XmlLookupEntry> = values
+        .iter()
+        .map(nvapi::xml::XmlLookupEntry::from)
+        .collect();
+
+    assert_eq!(nvapi::xml::find_entry(&list, "windows 11").unwrap().id, 135);
+    assert_eq!(nvapi::xml::find_entry(&list, "Linux 64").unwrap().id, 12);
+    assert_eq!(list[0].code.as_deref(), Some("10.0"));
+    assert!(nvapi::xml::find_entry(&list, "FreeBSD").is_err());
+    assert!(nvapi::xml::find_entry(&list, "windows")
+        .unwrap_err()
+        .contains("ambiguous"));
+}
+
+const DRIVER_PAGE: &str = r#"<html><body>
+<table>
+<tr><td class="contentsummaryleft">Version:</td><td id="tdVersion">516.94&nbsp;&nbsp;<sup>WHQL</sup></td></tr>
+<tr><td class="contentsummaryleft">Release Date:</td><td id="tdReleaseDate">2022.8.9</td></tr>
+<tr><td class="contentsummaryleft">Operating System:</td><td id="tdOSName">Windows 10 64-bit, Windows 11</td></tr>
+<tr><td class="contentsummaryleft">File Size:</td><td id="tdFileSize">815.98 MB</td></tr>
+</table>
+<a id="lnkDwnldBtn" href="/content/DriverDownloads/confirmation.php?url=/Windows/516.94/516.94-desktop-win10-win11-64bit-international-dch-whql.exe&lang=us&type=GeForce"><img src="download.png"></a>
+<div id="tab1_content"><p><b>Game Ready for Madden NFL 23</b></p><p>Includes support for the latest titles.</p></div>
+<div id="tab2_content"><p><b>GeForce RTX 30 Series:</b><br />NVIDIA GeForce RTX 3090 Ti, NVIDIA GeForce RTX 3090, NVIDIA GeForce RTX 3070</p>
+<p><b>GeForce 16 Series:</b><br />NVIDIA GeForce GTX 1660 SUPER, NVIDIA GeForce GTX 1650</p></div>
+</body></html>"#;
+
+#[test]
+fn test_driver_page_parsing() {
+    use nvapi::release::{parse_process_driver, DriverPageError, DriverRelease};
+
+    let release = DriverRelease::parse(DRIVER_PAGE).unwrap();
+    assert_eq!(release.version, nvapi::DriverVersion::new(516, 94));
+    assert_eq!(release.release_date.as_deref(), Some("2022.8.9"));
+    assert_eq!(release.file_size.as_deref(), Some("815.98 MB"));
+    assert_eq!(release.channel, nvapi::DriverChannels::GameReady);
+    assert!(release
+        .highlights
+        .starts_with("Game Ready for Madden NFL 23"));
+    assert_eq!(release.supported_products.len(), 5);
+    assert_eq!(release.supported_products[0].name, "GeForce RTX 3090 Ti");
+    assert_eq!(
+        release.download_url,
+        "https://international.download.nvidia.com/Windows/516.94/516.94-desktop-win10-win11-64bit-international-dch-whql.exe"
+    );
+
+    assert_eq!(
+        DriverRelease::parse("<html>Page moved</html>"),
+        Err(DriverPageError::UnknownLayout("version"))
+    );
+    assert_eq!(
+        parse_process_driver("//www.nvidia.com/download/driverResults.aspx/193095/en-us").unwrap(),
+        "https://www.nvidia.com/download/driverResults.aspx/193095/en-us"
+    );
+    assert!(parse_process_driver("<html>error</html>").is_err());
+}
+
+#[test]
+fn test_driver_supports_product() {
+    let release = nvapi::release::DriverRelease::parse(DRIVER_PAGE).unwrap();
+    let entry = |name: &str, series: &str| nvapi::xml::XmlGpuEntry {
+        name: name.to_string(),
+        series: 0,
+        id: 0,
+        series_name: Some(series.to_string()),
+    };
+
+    assert_eq!(
+        release.supported_products[3].series.as_deref(),
+        Some("GeForce 16 Series")
+    );
+    assert!(release.supports(&entry("GeForce GTX 1660 SUPER", "GeForce 16 Series")));
+    assert!(!release.supports(&entry("GeForce GTX 1660 Ti", "GeForce 16 Series")));
+    // only desktop GPUs are listed
+    assert!(!release.supports(&entry("GeForce GTX 1650", "GeForce 16 Series (Notebooks)")));
+
+    let list = vec![
+        entry("GeForce RTX 3070", "GeForce RTX 30 Series"),
+        entry("GeForce RTX 2070", "GeForce RTX 20 Series"),
+    ];
+    assert_eq!(release.supported_entries(&list), vec![list[0].clone()]);
+
+    // Products without a model number only match by their whole name
+    let mut release = release;
+    release.supported_products = vec![nvapi::release::SupportedProduct {
+        series: Some("NVIDIA TITAN Series".to_string()),
+        name: "TITAN RTX".to_string(),
+    }];
+    assert!(release.supports(&entry("NVIDIA TITAN RTX", "NVIDIA TITAN Series")));
+    assert!(!release.supports(&entry("NVIDIA TITAN Xp", "NVIDIA TITAN Series")));
+}
+
+#[test]
+fn test_ajax_driver_service_parsing() {
+    use nvapi::source::AjaxDriverService;
+
+    let json = r#"{"Success":"1","IDS":[{"downloadInfo":{"Success":"1","ID":"193095","Version":"516.94",
+        "Name":"GeForce%20Game%20Ready%20Driver","ReleaseDateTime":"Tue Aug 09, 2022",
+        "DownloadURL":"https://us.download.nvidia.com/Windows/516.94/516.94-desktop-win10-win11-64bit-international-dch-whql.exe",
+        "DownloadURLFileSize":"815.98 MB","OsName":"Windows%2010%2064-bit","IsCRD":"0",
+        "ReleaseNotes":"%3Cp%3EGame%20Ready%20for%20Madden%20NFL%2023%3C%2Fp%3E"}}]}"#;
+    let releases = AjaxDriverService::parse(json).unwrap();
+
+    assert_eq!(releases.len(), 1);
+    let release = &releases[0];
+    assert_eq!(release.version, nvapi::DriverVersion::new(516, 94));
+    assert_eq!(release.os.as_deref(), Some("Windows 10 64-bit"));
+    assert_eq!(release.highlights, "Game Ready for Madden NFL 23");
+    assert_eq!(release.channel, nvapi::DriverChannels::GameReady);
+
+    assert!(AjaxDriverService::parse(r#"{"Success":"0"}"#).is_err());
+
+    // The JSON API doesn't know the lid, other languages only go to the website
+    let query = |language: u16| nvapi::source::DriverQuery {
+        gpu: nvapi::xml::XmlGpuEntry {
+            name: "GeForce RTX 3070".to_string(),
+            series: 120,
+            id: 933,
+            series_name: None,
+        },
+        driver: nvapi::Driver {
+            version: String::new(),
+            channel: nvapi::DriverChannels::GameReady,
+            platform: nvapi::DriverPlatform::Desktop,
+            edition: nvapi::DriverEdition::DCH,
+        },
+        target: nvapi::LookupTarget {
+            language,
+            ..Default::default()
+        },
+        support: nvapi::legacy::Support::Current,
+    };
+    let names = |language| {
+        nvapi::source::Fallback::nvidia(query(language))
+            .sources
+            .iter()
+            .map(|source| source.name())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(names(1), vec!["AjaxDriverService", "NVIDIA website"]);
+    assert_eq!(names(7), vec!["NVIDIA website"]);
+}
+
+#[test]
+fn test_local_driver_source() {
+    let source = nvapi::source::from_arg(
+        "/mnt/share/drivers/516.94-desktop-win10-win11-64bit-international-nsd-dch-whql.exe",
+    );
+    assert_eq!(source.name(), "local file");
+
+    let release = bo!(source.release()).unwrap();
+    assert_eq!(release.version, nvapi::DriverVersion::new(516, 94));
+    assert_eq!(release.channel, nvapi::DriverChannels::Studio);
+    assert!(bo!(source.acquire()).is_err());
+
+    let source = nvapi::source::from_arg("https://example.com/driver.exe");
+    assert_eq!(source.name(), "direct link");
+    assert!(bo!(source.release()).is_err());
+
+    use nvapi::DriverVersion;
+    assert_eq!(
+        DriverVersion::from_file_name("GeForce_516.94_WHQL.exe"),
+        Some(DriverVersion::new(516, 94))
+    );
+    assert_eq!(DriverVersion::from_file_name("setup-51.exe"), None);
+}
+
+#[test]
+fn test_scan_candidates() {
+    let options = nvapi::scan::ScanOptions {
+        minors: 59..=60,
+        ..Default::default()
+    };
+    let drivers = nvapi::scan::candidates(516, &options);
+
+    // 2 versions, 2 channels, 2 platforms and 2 editions
+    assert_eq!(drivers.len(), 16);
+    assert_eq!(drivers[0].version, "516.59");
+    assert_eq!(drivers[15].version, "516.60");
+    assert!(nvapi::candidate_links(&drivers[0])
+        .iter()
+        .any(|(_, link)| link
+            .ends_with("516.59-desktop-win10-win11-64bit-international-dch-whql.exe")));
+
+    // Vulkan betas keep their main link before R471
+    let vulkan = Driver {
+        version: "457.44".to_string(),
+        channel: nvapi::DriverChannels::VulkanBeta,
+        platform: nvapi::DriverPlatform::Desktop,
+        edition: nvapi::DriverEdition::DCH,
+    };
+    assert!(nvapi::candidate_links(&vulkan)
+        .iter()
+        .any(|(_, link)| link.ends_with("/vulkan-beta-45744-windows")));
+
+    // Hotfixes use one link for both platforms, it's only probed once
+    let options = nvapi::scan::ScanOptions {
+        minors: 79..=79,
+        channels: vec![nvapi::DriverChannels::Hotfix],
+        ..Default::default()
+    };
+    let hits = nvapi::scan::scan_candidates(531, &options);
+    assert!(!hits.is_empty());
+    for hit in &hits {
+        assert_eq!(
+            hits.iter().filter(|other| other.link == hit.link).count(),
+            1
+        );
+    }
+}
+
+#[test]
+fn test_extra_channels() {
+    let driver = Driver {
+        version: "531.79".to_string(),
+        channel: nvapi::DriverChannels::Hotfix,
+        platform: nvapi::DriverPlatform::Desktop,
+        edition: nvapi::DriverEdition::DCH,
+    };
+    assert!(nvapi::candidate_links(&driver).iter().any(|(_, link)| link
+        == "https://international.download.nvidia.com/Windows/531.79hf/531.79-desktop-notebook-win10-win11-64bit-international-dch.hf.exe"));
+    assert_eq!(driver.channel.into_api(), None);
+    assert_eq!(
+        nvapi::DriverChannels::VulkanBeta.to_string(),
+        "-vulkan-beta"
+    );
+
+    let page = r#"<a href="https://developer.nvidia.com/downloads/vulkan-beta-53867-windows">Windows 538.67</a>
+        <a href="https://developer.nvidia.com/downloads/vulkan-beta-53862-windows">Windows 538.62</a>"#;
+    let (version, link) = nvapi::channels::parse_vulkan_driver_page(page).unwrap();
+    assert_eq!(version, nvapi::DriverVersion::new(538, 67));
+    assert_eq!(
+        link,
+        "https://developer.nvidia.com/downloads/vulkan-beta-53867-windows"
+    );
+}
+
+#[test]
+fn test_matrix_candidates() {
+    let entries = nvapi::scan::matrix_candidates(nvapi::DriverVersion::new(516, 59));
+
+    // Game Ready and Studio: 2 platforms and 2 Windows versions each, 516.59 is DCH only
+    let whql = entries
+        .iter()
+        .filter(|entry| entry.link.ends_with("-whql.exe"))
+        .count();
+    assert_eq!(whql, 8);
+    assert!(entries.iter().all(|entry| !entry.exists));
+    // Vulkan beta links are pages, their size means nothing
+    assert!(entries
+        .iter()
+        .all(|entry| entry.channel != nvapi::DriverChannels::VulkanBeta));
+
+    let mut links: Vec<&String> = entries.iter().map(|entry| &entry.link).collect();
+    links.sort();
+    links.dedup();
+    assert_eq!(links.len(), entries.len());
+}
+
+#[test]
+fn test_offline_rules() {
+    let driver = |version: &str, channel, edition| Driver {
+        version: version.to_string(),
+        channel,
+        platform: nvapi::DriverPlatform::Desktop,
+        edition,
+    };
+    use nvapi::{DriverChannels::*, DriverEdition::*};
+
+    assert!(driver("516.59", GameReady, STD).validate().is_err());
+    assert!(driver("399.24", GameReady, DCH).validate().is_err());
+    assert!(driver("417.71", Studio, DCH).validate().is_err());
+    assert!(driver("441.41", GameReady, STD).validate().is_ok());
+    assert!(bo!(nvapi::new_link(&driver("516.59", GameReady, STD))).is_err());
+
+    // "win10-win11" packages don't exist before Windows 11
+    let links = nvapi::candidate_links(&driver("441.41", GameReady, STD));
+    assert_eq!(links.len(), 1);
+    assert!(links[0].1.contains("-win10-64bit"));
+    assert!(nvapi::candidate_links(&driver("516.59", GameReady, STD)).is_empty());
+}
+
+#[test]
+fn test_cuda_compatibility() {
+    use nvapi::cuda::{self, CudaVersion};
+    use nvapi::legacy::{DriverBranch, Support};
+
+    let cuda: CudaVersion = "12.2.1".parse().unwrap();
+    assert_eq!(cuda, CudaVersion::new(12, 2));
+    assert_eq!(
+        cuda::min_driver(cuda),
+        Some(nvapi::DriverVersion::new(536, 25))
+    );
+    assert_eq!(
+        cuda::max_cuda(nvapi::DriverVersion::new(516, 59)),
+        Some(CudaVersion::new(11, 7))
+    );
+    assert_eq!(
+        cuda::max_cuda_for_branch(DriverBranch(535)),
+        Some(CudaVersion::new(12, 2))
+    );
+    assert_eq!(
+        cuda::max_cuda(nvapi::DriverVersion::new(581, 15)),
+        Some(CudaVersion::new(13, 0))
+    );
+    // 12.7 was never released, 13.9 isn't in the table yet so its minimum is unknown
+    assert_eq!(
+        cuda::min_driver(CudaVersion::new(12, 7)),
+        Some(nvapi::DriverVersion::new(560, 76))
+    );
+    assert_eq!(cuda::min_driver(CudaVersion::new(13, 9)), None);
+    assert_eq!(cuda::min_driver(CudaVersion::new(8, 0)), None);
+
+    let release = |version: &str| nvapi::release::DriverRelease {
+        version: version.parse().unwrap(),
+        release_date: None,
+        file_size: None,
+        channel: nvapi::DriverChannels::Studio,
+        os: None,
+        highlights: String::new(),
+        supported_products: Vec::new(),
+        download_url: String::new(),
+    };
+    let releases = vec![release("537.13"), release("536.23"), release("531.61")];
+    let picked = cuda::pick_release(releases.clone(), cuda, &Support::Current).unwrap();
+    assert_eq!(picked.version, nvapi::DriverVersion::new(537, 13));
+    assert!(
+        cuda::pick_release(releases.clone(), CudaVersion::new(12, 4), &Support::Current).is_err()
+    );
+    // Kepler can't get past R470
+    let kepler = Support::Legacy(DriverBranch(470));
+    let err = cuda::pick_release(releases, cuda, &kepler).unwrap_err();
+    assert!(err
+        .to_string()
+        .contains("R470, which supports CUDA up to 11.4"));
+}
+
+#[test]
+fn test_component_catalog() {
+    use crate::setup::catalog::Catalog;
+
+    let mut catalog = Catalog::builtin();
+    assert!(catalog.get("telemetry").is_some());
+    assert!(!catalog.get("optimus").unwrap().safe_to_remove);
+
+    let user = Catalog::parse(
+        r#"
+version = 1
+
+[[component]]
+id = "telemetry"
+name = "Telemetry"
+paths = ["NvTelemetry"]
+safe_to_remove = true
+
+[[component]]
+id = "vulkan-layers"
+name = "Old Vulkan layers"
+paths = ["Display.Driver/nvoglv6*.json", "Display.Driver/nvoglv*.json"]
+max_version = "470.99"
+"#,
+    )
+    .unwrap();
+    catalog.merge(user).unwrap();
+    assert_eq!(catalog.get("telemetry").unwrap().paths, vec!["NvTelemetry"]);
+    assert!(!catalog.get("vulkan-layers").unwrap().safe_to_remove);
+
+    let newer = nvapi::DriverVersion::new(516, 59);
+    assert!(!catalog
+        .get("vulkan-layers")
+        .unwrap()
+        .applies_to(Some(newer)));
+    assert!(catalog.get("vulkan-layers").unwrap().applies_to(None));
+    assert!(Catalog::parse("version = 99").is_err());
+
+    // Folders some versions use
+    let nvapp = catalog.get("nvapp").unwrap();
+    assert_eq!(
+        nvapp.paths_for(Some(nvapi::DriverVersion::new(552, 12))),
+        vec!["NvApp"]
+    );
+    assert_eq!(nvapp.paths_for(None), vec!["NvApp", "NvApp.*"]);
+    assert!(nvapp.covers(std::path::Path::new("NvApp.MessageBus")));
+    assert!(!nvapp.applies_to(Some(newer)));
+    for id in [
+        "physx",
+        "hdaudio",
+        "ppc",
+        "nvcontainer",
+        "nvcpl",
+        "ansel",
+        "nvbackend",
+        "shield-controller",
+    ] {
+        assert!(!catalog.get(id).unwrap().description.is_empty(), "{id}");
+    }
+    assert!(Catalog::parse("version = 1\n[[component]]\nid = \"x\"").is_err());
+
+    // Globs are expanded against the extracted tree, wherever it is
+    let root = std::env::temp_dir().join("nvix-catalog-[test]");
+    let _ = std::fs::remove_dir_all(&root);
+    std::fs::create_dir_all(root.join("Display.Driver")).unwrap();
+    std::fs::write(root.join("Display.Driver/nvoglv64.json"), "").unwrap();
+    std::fs::write(root.join("Display.Driver/nvoglv32.json"), "").unwrap();
+    let components = catalog.components(&root, Some(nvapi::DriverVersion::new(466, 77)));
+    let layers = components.iter().find(|c| c.id == "vulkan-layers").unwrap();
+    assert_eq!(layers.paths.len(), 2);
+    let telemetry = components.iter().find(|c| c.id == "telemetry").unwrap();
+    assert_eq!(telemetry.paths, vec![root.join("NvTelemetry")]);
+    std::fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn test_package_manifests() {
+    use crate::setup::package::Package;
+
+    let root = std::env::temp_dir().join("nvix-package-test");
+    let _ = std::fs::remove_dir_all(&root);
+    std::fs::create_dir_all(root.join("Display.Driver")).unwrap();
+    std::fs::create_dir_all(root.join("GFExperience/PrivacyPolicy")).unwrap();
+    std::fs::write(
+        root.join("setup.cfg"),
+        r#"<?xml version="1.0" encoding="utf-8"?>
+<setup title="${{ProductTitle}}" version="516.59">
+  <properties>
+    <string name="ProductTitle" value="NVIDIA Graphics Driver"/>
+  </properties>
+  <manifest>
+    <file name="${{EulaHtmlFile}}"/>
+    <file name="ListDevices.txt"/>
+  </manifest>
+</setup>"#,
+    )
+    .unwrap();
+    std::fs::write(
+        root.join("Display.Driver/DisplayDriver.nvi"),
+        r#"<?xml version="1.0" encoding="utf-8"?>
+<nvi name="Display.Driver" title="${{title}}" version="516.59" disposition="demand" hidden="false">
+  <strings>
+    <string name="title" value="Graphics Driver"/>
+  </strings>
+  <dependencies>
+    <package type="requires" package="Display.PhysX"/>
+    <package type="before" package="Display.NView"/>
+  </dependencies>
+  <phases>
+    <standard phase="createInstallerPackages" when="install"/>
+    <standard phase="runInstaller" when="install"/>
+  </phases>
+</nvi>"#,
+    )
+    .unwrap();
+    std::fs::write(
+        root.join("GFExperience/PrivacyPolicy/PrivacyPolicy.nvi"),
+        r#"<nvi name="PrivacyPolicy" title="Privacy Policy" hidden="true"/>"#,
+    )
+    .unwrap();
+
+    let package = Package::load(&root).unwrap();
+    assert_eq!(package.title.as_deref(), Some("NVIDIA Graphics Driver"));
+    assert_eq!(package.components.len(), 2);
+
+    let driver = package.get("Display.Driver").unwrap();
+    assert_eq!(driver.name, "Graphics Driver");
+    assert_eq!(driver.version.as_deref(), Some("516.59"));
+    assert_eq!(driver.requires().collect::<Vec<_>>(), vec!["Display.PhysX"]);
+
+    let policy = package.get("PrivacyPolicy").unwrap();
+    assert!(policy.hidden);
+    assert_eq!(
+        package
+            .in_folder(std::path::Path::new("GFExperience"))
+            .count(),
+        1
+    );
+
+    std::fs::remove_file(root.join("setup.cfg")).unwrap();
+    assert!(Package::load(&root).is_err());
+    std::fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn test_strip_dependencies() {
+    use crate::setup::{
+        catalog::Catalog,
+        graph::DependencyGraph,
+        package::{Package, PackageComponent},
+    };
+    use std::path::Path;
+
+    let component =
+        |xml: &str, manifest: &str| PackageComponent::parse(xml, Path::new(manifest)).unwrap();
+    let package = Package {
+        components: vec![
+            component(
+                r#"<nvi name="Display.Driver"/>"#,
+                "Display.Driver/DisplayDriver.nvi",
+            ),
+            component(
+                r#"<nvi name="Display.Optimus"/>"#,
+                "Display.Optimus/Optimus.nvi",
+            ),
+            component(
+                r#"<nvi name="NvModuleTracker"/>"#,
+                "NvModuleTracker/NvModuleTracker.nvi",
+            ),
+            component(
+                r#"<nvi name="GFExperience"><dependencies><package type="requires" package="NvContainer"/></dependencies></nvi>"#,
+                "GFExperience/GFExperience.nvi",
+            ),
+            component(
+                r#"<nvi name="ShadowPlay"><dependencies><package type="requires" package="GFExperience"/><package type="after" package="Display.Driver"/></dependencies></nvi>"#,
+                "ShadowPlay/ShadowPlay.nvi",
+            ),
+            component(
+                r#"<nvi name="NvContainer"/>"#,
+                "NvContainer/NvContainer.nvi",
+            ),
+        ],
+        ..Default::default()
+    };
+    let graph = DependencyGraph::build(&package, &Catalog::builtin());
+
+    // GFE needs the module tracker
+    let check = graph.check(["module-tracker"]);
+    assert!(!check.is_safe());
+    assert_eq!(check.conflicts[0].kept, "gfe");
+    assert!(check.conflicts[0].to_string().contains("(gfe) requires"));
+    assert!(check.pulled_in.contains("gfe"));
+    assert!(check.pulled_in.contains("shadowplay"));
+    let check = graph.check(["module-tracker", "gfe"]);
+    assert!(check.conflicts.iter().any(|c| c.kept == "shadowplay"));
+    assert!(graph
+        .check(["module-tracker", "gfe", "shadowplay"])
+        .is_safe());
+
+    // Removing a package component pulls in everything depending on it
+    let check = graph.check(["NvContainer"]);
+    assert!(check.pulled_in.contains("GFExperience"));
+    assert!(check.pulled_in.contains("ShadowPlay"));
+    assert!(!check.pulled_in.contains("Display.Driver")); // only ordered after it
+
+    let check = graph.check(["optimus"]);
+    assert_eq!(check.unsafe_removals, vec!["optimus"]);
+    assert!(check.removed.contains("Display.Optimus"));
+
+    // Components without folders in the package don't conflict
+    assert!(graph.check(["telemetry"]).is_safe());
+}
+
+#[test]
+fn test_setup_cfg_patch() {
+    use crate::setup::patch::{patch_xml, Patch, PatchRules};
+
+    let setup = "<?xml version=\"1.0\" encoding=\"utf-8\"?>\r\n<setup title=\"${{ProductTitle}}\">\r\n  <!-- keep me -->\r\n  <manifest>\r\n    <file name=\"${{EulaHtmlFile}}\"/>\r\n    <file name=\"${{PrivacyPolicyFile}}\" />\r\n    <file name=\"ListDevices.txt\"/>\r\n    <file name=\"NvTelemetry\\NvTelemetry.nvi\"/>\r\n  </manifest>\r\n  <dependencies><package type=\"requires\" package=\"Display.Optimus\">\r\n    <note/>\r\n  </package><package type=\"requires\" package=\"Display.Driver\"/></dependencies>\r\n</setup>\r\n";
+    let mut rules = PatchRules::default();
+    rules.packages.insert("Display.Optimus".to_string());
+    rules.add_folder(std::path::Path::new("NvTelemetry"));
+
+    let patched = patch_xml(setup, &rules).unwrap();
+    assert_eq!(
+        patched,
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?>\r\n<setup title=\"${{ProductTitle}}\">\r\n  <!-- keep me -->\r\n  <manifest>\r\n    <file name=\"ListDevices.txt\"/>\r\n  </manifest>\r\n  <dependencies><package type=\"requires\" package=\"Display.Driver\"/></dependencies>\r\n</setup>\r\n"
+    );
+    assert_eq!(patch_xml(&patched, &rules).unwrap(), patched);
+
+    let patch = Patch {
+        path: "setup.cfg".into(),
+        original: setup.to_string(),
+        patched,
+    };
+    let diff = patch.diff();
+    assert!(diff.contains("    5 -    <file name=\"${{EulaHtmlFile}}\"/>"));
+    assert_eq!(diff.lines().filter(|line| line.contains(" -")).count(), 6);
+    assert_eq!(diff.lines().filter(|line| line.contains(" +")).count(), 1);
+
+    // Removing a nested component keeps the rest of its parent folder
+    use crate::setup::{
+        package::{Package, PackageComponent},
+        quarantine::{Quarantine, QuarantineEntry, QuarantineManifest},
+    };
+    let component = |xml: &str, manifest: &str| {
+        PackageComponent::parse(xml, std::path::Path::new(manifest)).unwrap()
+    };
+    let package = Package {
+        components: vec![
+            component(
+                r#"<nvi name="GFExperience"/>"#,
+                "GFExperience/GFExperience.nvi",
+            ),
+            component(
+                r#"<nvi name="PrivacyPolicy"/>"#,
+                "GFExperience/PrivacyPolicy/PrivacyPolicy.nvi",
+            ),
+        ],
+        ..Default::default()
+    };
+    let setup = "<setup>\n  <file name=\"GFExperience\\GFExperience.nvi\"/>\n  <file name=\"GFExperience\\PrivacyPolicy\\PrivacyPolicy.nvi\"/>\n</setup>\n";
+    let nested = "<setup>\n  <file name=\"GFExperience\\GFExperience.nvi\"/>\n</setup>\n";
+    let removed = ["PrivacyPolicy".to_string()].into_iter().collect();
+    let rules = PatchRules::for_removed(&package, &removed);
+    assert_eq!(patch_xml(setup, &rules).unwrap(), nested);
+
+    let quarantine = Quarantine {
+        root: "NVIX".into(),
+        dir: "NVIX.quarantine".into(),
+        manifest: QuarantineManifest {
+            version: None,
+            entries: vec![QuarantineEntry {
+                id: "PrivacyPolicy".to_string(),
+                name: "Privacy Policy".to_string(),
+                paths: vec!["GFExperience/PrivacyPolicy".into()],
+                files: Vec::new(),
+                packages: vec!["PrivacyPolicy".to_string()],
+                bytes: 0,
+            }],
+            originals: Vec::new(),
+        },
+    };
+    assert_eq!(patch_xml(setup, &quarantine.patch_rules()).unwrap(), nested);
+}
+
+#[test]
+fn test_strip_report() {
+    use crate::setup::{self, catalog::Catalog, package::Package, ComponentStatus, StripOptions};
+
+    let root = std::env::temp_dir().join("nvix-strip-test");
+    let _ = std::fs::remove_dir_all(&root);
+    for folder in [
+        "NvTelemetry",
+        "NvModuleTracker",
+        "GFExperience",
+        "Display.Driver",
+    ] {
+        std::fs::create_dir_all(root.join(folder)).unwrap();
+        std::fs::write(
+            root.join(folder).join(format!("{folder}.nvi")),
+            format!(r#"<nvi name="{folder}"/>"#),
+        )
+        .unwrap();
+    }
+    std::fs::write(root.join("NvTelemetry/NvTelemetry.dll"), vec![0u8; 1000]).unwrap();
+    std::fs::write(
+        root.join("setup.cfg"),
+        "<setup version=\"516.59\">\n  <manifest>\n    <file name=\"${{EulaHtmlFile}}\"/>\n    <file name=\"NvTelemetry\\NvTelemetry.nvi\"/>\n  </manifest>\n</setup>\n",
+    )
+    .unwrap();
+
+    let package = Package::load(&root).unwrap();
+    let catalog = Catalog::builtin();
+    let selection = |ids: &[&str]| ids.iter().map(|id| id.to_string()).collect::<Vec<_>>();
+    let strip =
+        |ids: &[&str], options| bo!(setup::strip(&package, &catalog, &selection(ids), options));
+
+    // Kept components that depend on removed ones stop the strip
+    assert!(strip(&["module-tracker"], StripOptions::default()).is_err());
+    // Forcing it reports the conflicts instead
+    let forced = StripOptions {
+        force: true,
+        dry_run: true,
+        ..Default::default()
+    };
+    let report = strip(&["module-tracker"], forced).unwrap();
+    assert!(
+        report.warnings.iter().any(|w| w.contains("requires")),
+        "{:?}",
+        report.warnings
+    );
+    assert!(strip(&["nothing"], StripOptions::default()).is_err());
+
+    let dry_run = StripOptions {
+        dry_run: true,
+        ..Default::default()
+    };
+    let report = strip(&["telemetry", "frameview", "nvapp"], dry_run).unwrap();
+    assert!(root.join("NvTelemetry").exists());
+    assert_eq!(
+        report.bytes_freed(),
+        1000 + r#"<nvi name="NvTelemetry"/>"#.len() as u64
+    );
+    assert_eq!(report.patches.len(), 1);
+    let status = |id: &str| {
+        report
+            .components
+            .iter()
+            .find(|component| component.id == id)
+            .unwrap()
+            .status
+            .clone()
+    };
+    assert_eq!(status("telemetry"), ComponentStatus::Removed);
+    assert_eq!(status("frameview"), ComponentStatus::Missing);
+    assert_eq!(status("gfe"), ComponentStatus::Kept);
+    // The NVIDIA App is only in newer drivers
+    assert_eq!(status("nvapp"), ComponentStatus::NotApplicable);
+
+    let permanent = StripOptions {
+        permanent: true,
+        ..Default::default()
+    };
+    let report = strip(&["telemetry"], permanent).unwrap();
+    assert!(!report.has_failures());
+    assert!(!setup::quarantine::Quarantine::dir_for(&root).exists());
+    assert!(!root.join("NvTelemetry").exists());
+    assert!(root.join("GFExperience").exists());
+    assert_eq!(
+        std::fs::read_to_string(root.join("setup.cfg")).unwrap(),
+        "<setup version=\"516.59\">\n  <manifest>\n  </manifest>\n</setup>\n"
+    );
+    std::fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn test_quarantine_restore() {
+    use crate::setup::{
+        self, catalog::Catalog, package::Package, quarantine::Quarantine, StripOptions,
+    };
+
+    let root = std::env::temp_dir().join("nvix-quarantine-test");
+    let _ = std::fs::remove_dir_all(&root);
+    let _ = std::fs::remove_dir_all(Quarantine::dir_for(&root));
+    for folder in ["NvTelemetry", "FrameViewSDK", "Display.Driver"] {
+        std::fs::create_dir_all(root.join(folder)).unwrap();
+        std::fs::write(
+            root.join(folder).join(format!("{folder}.nvi")),
+            format!(r#"<nvi name="{folder}"/>"#),
+        )
+        .unwrap();
+    }
+    std::fs::write(root.join("Display.Driver/nvlddmkm.sys"), vec![0u8; 10]).unwrap();
+    std::fs::write(root.join("Display.Driver/nvapi.pdb"), vec![0u8; 10]).unwrap();
+    let setup_cfg = "<setup>\n  <manifest>\n    <file name=\"${{EulaHtmlFile}}\"/>\n    <file name=\"NvTelemetry\\NvTelemetry.nvi\"/>\n    <file name=\"FrameViewSDK\\FrameViewSDK.nvi\"/>\n    <file name=\"Display.Driver\\nvlddmkm.sys\"/>\n    <file name=\"Display.Driver\\nvapi.pdb\"/>\n  </manifest>\n</setup>\n";
+    std::fs::write(root.join("setup.cfg"), setup_cfg).unwrap();
+
+    let mut catalog = Catalog::builtin();
+    let symbols = "version = 1\n[[component]]\nid = \"symbols\"\nname = \"Debug Symbols\"\nfiles = [\"Display.Driver/*.pdb\"]\nsafe_to_remove = true";
+    catalog.merge(Catalog::parse(symbols).unwrap()).unwrap();
+    let package = Package::load(&root).unwrap();
+    let selection = vec![
+        "telemetry".to_string(),
+        "frameview".to_string(),
+        "symbols".to_string(),
+    ];
+    bo!(setup::strip(
+        &package,
+        &catalog,
+        &selection,
+        StripOptions::default()
+    ))
+    .unwrap();
+    assert!(!root.join("NvTelemetry").exists());
+
+    let mut quarantine = Quarantine::open(&root).unwrap();
+    assert_eq!(quarantine.entries().len(), 3);
+    assert!(quarantine
+        .dir
+        .join("telemetry/NvTelemetry/NvTelemetry.nvi")
+        .exists());
+    assert!(quarantine.restore(&["gfe".to_string()]).is_err());
+
+    // setup.cfg is patched again for what's still in quarantine, the symbols only take their own files
+    let restored = quarantine.restore(&["telemetry".to_string()]).unwrap();
+    assert_eq!(restored, vec!["telemetry"]);
+    assert!(root.join("NvTelemetry/NvTelemetry.nvi").exists());
+    assert_eq!(
+        std::fs::read_to_string(root.join("setup.cfg")).unwrap(),
+        "<setup>\n  <manifest>\n    <file name=\"NvTelemetry\\NvTelemetry.nvi\"/>\n    <file name=\"Display.Driver\\nvlddmkm.sys\"/>\n  </manifest>\n</setup>\n"
+    );
+
+    // The manifest survives reopening
+    let mut quarantine = Quarantine::open(&root).unwrap();
+    assert_eq!(quarantine.entries().len(), 2);
+    quarantine.restore(&[]).unwrap();
+    assert!(root.join("FrameViewSDK").exists());
+    assert!(root.join("Display.Driver/nvapi.pdb").exists());
+    assert_eq!(
+        std::fs::read_to_string(root.join("setup.cfg")).unwrap(),
+        setup_cfg
+    );
+    assert!(quarantine.entries().is_empty());
+
+    // Extracting another driver to the same folder leaves the quarantine of the old one behind
+    let strip = |selection: &[String]| {
+        let package = Package::load(&root).unwrap();
+        bo!(setup::strip(
+            &package,
+            &catalog,
+            selection,
+            StripOptions::default()
+        ))
+        .unwrap();
+    };
+    strip(&selection[..1]);
+    std::fs::create_dir_all(root.join("NvTelemetry")).unwrap();
+    std::fs::write(root.join("NvTelemetry/NvTelemetry.nvi"), "<nvi/>").unwrap();
+    let newer = setup_cfg.replace("<setup>", "<setup version=\"531.79\">");
+    std::fs::write(root.join("setup.cfg"), &newer).unwrap();
+    strip(&selection[..1]);
+    let mut quarantine = Quarantine::open(&root).unwrap();
+    assert_eq!(quarantine.manifest.version.as_deref(), Some("531.79"));
+    quarantine.restore(&[]).unwrap();
+    assert_eq!(
+        std::fs::read_to_string(root.join("setup.cfg")).unwrap(),
+        newer
+    );
+
+    std::fs::remove_dir_all(&root).unwrap();
+    let _ = std::fs::remove_dir_all(Quarantine::dir_for(&root));
+}
+
+#[test]
+fn test_strip_profiles() {
+    use crate::setup::{
+        catalog::Catalog,
+        graph::DependencyGraph,
+        package::{Package, PackageComponent},
+    };
+
+    let catalog = Catalog::builtin();
+    let version = Some(nvapi::DriverVersion::new(566, 14));
+
+    // A package with a folder for every component of the catalog
+    let mut package = Package::default();
+    for component in &catalog.components {
+        let folder = component.paths[0].clone();
+        let manifest = format!("{folder}/{folder}.nvi");
+        let xml = format!(r#"<nvi name="{folder}"/>"#);
+        package
+            .components
+            .push(PackageComponent::parse(&xml, std::path::Path::new(&manifest)).unwrap());
+    }
+    let graph = DependencyGraph::build(&package, &catalog);
+
+    for id in ["minimal", "gaming", "workstation", "laptop-safe"] {
+        let profile = catalog.profile(id).unwrap();
+        let selection = profile.selection(&catalog, version);
+        let check = graph.check(selection.iter().map(String::as_str));
+        assert!(check.is_safe(), "{id}: {check}");
+        assert!(!selection.contains(&"optimus".to_string()), "{id}");
+        assert!(selection.contains(&"telemetry".to_string()), "{id}");
+    }
+
+    let gaming = catalog
+        .profile("gaming")
+        .unwrap()
+        .selection(&catalog, version);
+    assert!(!gaming.contains(&"physx".to_string()));
+    assert!(gaming.contains(&"nvcpl".to_string()));
+    let summary = catalog.summary(&gaming, version);
+    assert!(
+        summary.contains("Keeping: PhysX, HD Audio, Optimus"),
+        "{summary}"
+    );
+
+    // A profile keeping a component the catalog doesn't have would strip it instead
+    let mut catalog = Catalog::builtin();
+    let typo = Catalog::parse(
+        r#"
+version = 1
+
+[[profile]]
+id = "typo"
+name = "Typo"
+keep = ["phsyx"]
+"#,
+    )
+    .unwrap();
+    assert!(catalog.merge(typo).is_err());
+}
+
+#[test]
+fn test_locale_pruning() {
+    use crate::setup::{
+        self, catalog::Catalog, locale, package::Package, quarantine::Quarantine, ComponentStatus,
+        StripOptions, LOCALES_ID,
+    };
+    use std::path::Path;
+
+    assert_eq!(locale::locale_of_name("license.de-DE.txt"), Some("de-de"));
+    assert_eq!(locale::locale_of_name("nvcpl_ja_JP.dll"), Some("ja-jp"));
+    assert_eq!(
+        locale::locale_of(Path::new("EULA/fr-FR/eula.txt")),
+        Some("fr-fr")
+    );
+    assert_eq!(locale::locale_of_name("Display.Driver"), None);
+    assert!(locale::removed_locales(&["klingon".to_string()]).is_err());
+    let removed = locale::removed_locales(&["ja-JP".to_string()]).unwrap();
+    assert!(!removed.contains(&"ja-jp") && !removed.contains(&"en-us"));
+
+    let root = std::env::temp_dir().join("nvix-locale-test");
+    let _ = std::fs::remove_dir_all(&root);
+    let _ = std::fs::remove_dir_all(Quarantine::dir_for(&root));
+    std::fs::create_dir_all(root.join("Display.Driver")).unwrap();
+    std::fs::create_dir_all(root.join("EULA/fr-FR")).unwrap();
+    std::fs::create_dir_all(root.join("EULA/en-US")).unwrap();
+    std::fs::create_dir_all(root.join("NvCpl")).unwrap();
+    std::fs::write(
+        root.join("Display.Driver/DisplayDriver.nvi"),
+        r#"<nvi name="Display.Driver"/>"#,
+    )
+    .unwrap();
+    std::fs::write(
+        root.join("Display.Driver/license.de-DE.txt"),
+        vec![0u8; 100],
+    )
+    .unwrap();
+    std::fs::write(root.join("NvCpl/nvcpl_de-DE.dll"), vec![0u8; 100]).unwrap();
+    std::fs::write(root.join("NvCpl/nvcpl_ja-JP.dll"), vec![0u8; 100]).unwrap();
+    std::fs::write(root.join("EULA/fr-FR/eula.txt"), vec![0u8; 50]).unwrap();
+    std::fs::write(root.join("EULA/en-US/eula.txt"), vec![0u8; 50]).unwrap();
+    let setup_cfg = "<setup>\n  <strings>\n    <string name=\"Title\" locale=\"de-DE\" value=\"Grafiktreiber\"/>\n    <string name=\"Title\" value=\"Graphics Driver\"/>\n  </strings>\n  <manifest>\n    <file name=\"EULA\\fr-FR\\eula.txt\"/>\n    <file name=\"EULA\\en-US\\eula.txt\"/>\n    <file name=\"Display.Driver\\license.de-DE.txt\"/>\n  </manifest>\n</setup>\n";
+    std::fs::write(root.join("setup.cfg"), setup_cfg).unwrap();
+
+    let package = Package::load(&root).unwrap();
+    let strip = |options| bo!(setup::strip(&package, &Catalog::builtin(), &[], options));
+    let options = |dry_run| StripOptions {
+        dry_run,
+        keep_locales: Some(vec!["ja-JP".to_string()]),
+        ..Default::default()
+    };
+
+    let report = strip(options(true)).unwrap();
+    let languages = report
+        .components
+        .iter()
+        .find(|component| component.id == LOCALES_ID)
+        .unwrap();
+    assert_eq!(languages.status, ComponentStatus::Removed);
+    assert_eq!(languages.bytes, 150);
+    assert_eq!(report.bytes_freed(), 150);
+    assert!(root.join("EULA/fr-FR").exists());
+
+    strip(options(false)).unwrap();
+    assert!(!root.join("EULA/fr-FR").exists());
+    assert!(!root.join("NvCpl/nvcpl_de-DE.dll").exists());
+    assert!(root.join("NvCpl/nvcpl_ja-JP.dll").exists());
+    // The INF of the driver lists its files, they stay in setup.cfg as well
+    assert!(root.join("Display.Driver/license.de-DE.txt").exists());
+    assert!(root.join("EULA/en-US/eula.txt").exists());
+    assert_eq!(
+        std::fs::read_to_string(root.join("setup.cfg")).unwrap(),
+        "<setup>\n  <strings>\n    <string name=\"Title\" value=\"Graphics Driver\"/>\n  </strings>\n  <manifest>\n    <file name=\"EULA\\en-US\\eula.txt\"/>\n    <file name=\"Display.Driver\\license.de-DE.txt\"/>\n  </manifest>\n</setup>\n"
+    );
+
+    let mut quarantine = Quarantine::open(&root).unwrap();
+    quarantine.restore(&[LOCALES_ID.to_string()]).unwrap();
+    assert!(root.join("EULA/fr-FR/eula.txt").exists());
+    assert_eq!(
+        std::fs::read_to_string(root.join("setup.cfg")).unwrap(),
+        setup_cfg
+    );
+
+    std::fs::remove_dir_all(&root).unwrap();
+    std::fs::remove_dir_all(Quarantine::dir_for(&root)).unwrap();
+}
+
+#[test]
+fn test_custom_components() {
+    use crate::setup::{self, catalog::Catalog, package::Package, ComponentStatus, StripOptions};
+
+    let root = std::env::temp_dir().join("nvix-custom-test");
+    let _ = std::fs::remove_dir_all(&root);
+    std::fs::create_dir_all(root.join("Display.Driver")).unwrap();
+    std::fs::create_dir_all(root.join("Tools/Samples")).unwrap();
+    std::fs::write(
+        root.join("Display.Driver/DisplayDriver.nvi"),
+        r#"<nvi name="Display.Driver"/>"#,
+    )
+    .unwrap();
+    std::fs::write(root.join("Display.Driver/nvlddmkm.sys"), vec![0u8; 100]).unwrap();
+    std::fs::write(root.join("Display.Driver/nvlddmkm.pdb"), vec![0u8; 40]).unwrap();
+    std::fs::write(root.join("Display.Driver/nvapi.pdb"), vec![0u8; 60]).unwrap();
+    std::fs::write(root.join("Tools/Samples/sample.exe"), vec![0u8; 30]).unwrap();
+    let setup_cfg = "<setup>\n  <manifest>\n    <file name=\"Display.Driver\\nvlddmkm.sys\"/>\n    <file name=\"Display.Driver\\nvlddmkm.pdb\"/>\n    <file name=\"Display.Driver\\nvapi.pdb\"/>\n    <file name=\"Tools\\Samples\\sample.exe\"/>\n    <file name=\"Tools\\readme.txt\"/>\n  </manifest>\n</setup>\n";
+    std::fs::write(root.join("setup.cfg"), setup_cfg).unwrap();
+
+    let preset = |components: &str| {
+        let mut catalog = Catalog::builtin();
+        catalog
+            .merge(Catalog::parse(&format!("version = 1\n{components}")).unwrap())
+            .unwrap();
+        catalog
+    };
+    let catalog = preset(
+        r#"
+        [[component]]
+        id = "symbols"
+        name = "Debug Symbols"
+        files = ["Display.Driver/*.pdb"]
+        safe_to_remove = true
+
+        [[component]]
+        id = "samples"
+        name = "Samples"
+        paths = ["Tools/Sampl*", "Tools/Demos"]
+        safe_to_remove = true
+        "#,
+    );
+    let warnings = catalog.validate(&root, None, &[]).unwrap();
+    assert_eq!(
+        warnings,
+        vec!["Component \"samples\": \"Tools/Demos\" matches nothing".to_string()]
+    );
+    // Broken components are only an error if they are selected
+    let x = ["x".to_string()];
+    let outside = preset("[[component]]\nid = \"x\"\nname = \"X\"\npaths = [\"../x\"]");
+    assert!(outside.validate(&root, None, &x).is_err());
+    assert_eq!(
+        outside.validate(&root, None, &[]).unwrap(),
+        vec!["Component \"x\": \"../x\" is outside of the driver".to_string()]
+    );
+    assert!(preset("[[component]]\nid = \"x\"\nname = \"X\"")
+        .validate(&root, None, &x)
+        .is_err());
+
+    let package = Package::load(&root).unwrap();
+    let selection = ["symbols".to_string(), "samples".to_string()];
+    let report = bo!(setup::strip(
+        &package,
+        &catalog,
+        &selection,
+        StripOptions {
+            permanent: true,
+            ..Default::default()
+        }
+    ))
+    .unwrap();
+    assert_eq!(report.warnings, warnings);
+    let status = |id: &str| {
+        report
+            .components
+            .iter()
+            .find(|component| component.id == id)
+            .map(|component| (component.status.clone(), component.bytes))
+    };
+    assert_eq!(status("symbols"), Some((ComponentStatus::Removed, 100)));
+    assert_eq!(status("samples"), Some((ComponentStatus::Removed, 30)));
+    assert_eq!(report.bytes_freed(), 130);
+    assert!(root.join("Display.Driver/nvlddmkm.sys").exists());
+    assert!(!root.join("Display.Driver/nvapi.pdb").exists());
+    assert!(!root.join("Tools/Samples").exists());
+    // Folders without a manifest are patched out too, the rest of their parent stays
+    assert_eq!(
+        std::fs::read_to_string(root.join("setup.cfg")).unwrap(),
+        "<setup>\n  <manifest>\n    <file name=\"Display.Driver\\nvlddmkm.sys\"/>\n    <file name=\"Tools\\readme.txt\"/>\n  </manifest>\n</setup>\n"
+    );
+
+    // Restoring part of the quarantine keeps the other folders patched out
+    let quarantine_dir = setup::quarantine::Quarantine::dir_for(&root);
+    let _ = std::fs::remove_dir_all(&quarantine_dir);
+    std::fs::create_dir_all(root.join("Tools/Samples")).unwrap();
+    std::fs::create_dir_all(root.join("Tools/Demos")).unwrap();
+    std::fs::write(root.join("Display.Driver/nvapi.pdb"), vec![0u8; 60]).unwrap();
+    let with_demos = setup_cfg.replace(
+        "  </manifest>",
+        "    <file name=\"Tools\\Demos\\demo.exe\"/>\n  </manifest>",
+    );
+    std::fs::write(root.join("setup.cfg"), &with_demos).unwrap();
+    let package = Package::load(&root).unwrap();
+    bo!(setup::strip(
+        &package,
+        &catalog,
+        &selection,
+        StripOptions::default()
+    ))
+    .unwrap();
+    let mut quarantine = setup::quarantine::Quarantine::open(&root).unwrap();
+    quarantine.restore(&["symbols".to_string()]).unwrap();
+    assert_eq!(
+        std::fs::read_to_string(root.join("setup.cfg")).unwrap(),
+        "<setup>\n  <manifest>\n    <file name=\"Display.Driver\\nvlddmkm.sys\"/>\n    <file name=\"Display.Driver\\nvlddmkm.pdb\"/>\n    <file name=\"Display.Driver\\nvapi.pdb\"/>\n    <file name=\"Tools\\readme.txt\"/>\n  </manifest>\n</setup>\n"
+    );
+    quarantine.restore(&[]).unwrap();
+    assert_eq!(
+        std::fs::read_to_string(root.join("setup.cfg")).unwrap(),
+        with_demos
+    );
+
+    std::fs::remove_dir_all(&root).unwrap();
+    std::fs::remove_dir_all(&quarantine_dir).unwrap();
+}
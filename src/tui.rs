@@ -1,5 +1,6 @@
-///! For now this is for querying a driver. Likely in the future it will also be used to select older drivers, components and such.
+//! For now this is for querying a driver. Likely in the future it will also be used to select older drivers, components and such.
 use crate::nvapi::{xml::get_gpu_list, xml::XmlGpuEntry};
+use crate::setup::catalog::{Catalog, Profile};
 use crossterm::{
     self,
     event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode},
@@ -18,6 +19,7 @@ use tui::{
     Frame, Terminal,
 };
 
+/// Lets the user pick a GPU from NVIDIA's product list, for `--pick`
 pub async fn gpu_selector() -> Result<Option<XmlGpuEntry>, Box<dyn Error>> {
     // setup terminal
     crossterm::terminal::enable_raw_mode()?;
@@ -43,6 +45,95 @@ pub async fn gpu_selector() -> Result<Option<XmlGpuEntry>, Box<dyn Error>> {
     res
 }
 
+/// Lets the user pick a strip profile, showing what it removes before anything is stripped
+pub fn profile_selector(catalog: &Catalog) -> Result<Option<Profile>, Box<dyn Error>> {
+    crossterm::terminal::enable_raw_mode()?;
+    let mut stdout = std::io::stdout();
+    crossterm::execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
+    let backend = CrosstermBackend::new(stdout);
+    let mut terminal = Terminal::new(backend)?;
+
+    let mut profiles = StatefulList::with_items(catalog.profiles.clone());
+    profiles.next();
+    let res = run_profiles(&mut terminal, catalog, &mut profiles);
+
+    crossterm::terminal::disable_raw_mode()?;
+    crossterm::execute!(
+        terminal.backend_mut(),
+        LeaveAlternateScreen,
+        DisableMouseCapture
+    )?;
+    terminal.show_cursor()?;
+
+    res
+}
+
+fn run_profiles<B: Backend>(
+    terminal: &mut Terminal<B>,
+    catalog: &Catalog,
+    profiles: &mut StatefulList<Profile>,
+) -> Result<Option<Profile>, Box<dyn Error>> {
+    loop {
+        terminal.draw(|f| {
+            let chunks = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([Constraint::Percentage(50), Constraint::Percentage(50)].as_ref())
+                .split(f.size());
+
+            let items: Vec<ListItem> = profiles
+                .items
+                .iter()
+                .map(|p| ListItem::new(p.name.clone()))
+                .collect();
+            let list = List::new(items)
+                .block(
+                    Block::default()
+                        .borders(Borders::ALL)
+                        .title("Select a strip profile (Enter to select, q to skip)"),
+                )
+                .highlight_style(
+                    Style::default()
+                        .bg(Color::LightGreen)
+                        .add_modifier(Modifier::BOLD),
+                )
+                .highlight_symbol("> ");
+
+            let summary = match profiles
+                .state
+                .selected()
+                .and_then(|i| profiles.items.get(i))
+            {
+                Some(profile) => format!(
+                    "{}\n\n{}",
+                    profile.description,
+                    catalog.summary(&profile.selection(catalog, None), None)
+                ),
+                None => String::new(),
+            };
+            let summary = Paragraph::new(Text::from(summary))
+                .block(Block::default().borders(Borders::ALL).title("Components"))
+                .wrap(tui::widgets::Wrap { trim: true });
+
+            f.render_stateful_widget(list, chunks[0], &mut profiles.state);
+            f.render_widget(summary, chunks[1]);
+        })?;
+
+        if let Event::Key(key) = event::read()? {
+            match key.code {
+                KeyCode::Char('q') | KeyCode::Esc => return Ok(None),
+                KeyCode::Down => profiles.next(),
+                KeyCode::Up => profiles.previous(),
+                KeyCode::Enter => {
+                    if let Some(i) = profiles.state.selected() {
+                        return Ok(profiles.items.get(i).cloned());
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
 #[derive(Clone)]
 struct StatefulList<T> {
     state: ListState,
@@ -104,10 +195,10 @@ enum InputMode {
     Search,
 }
 
-impl<'a> App {
+impl App {
     async fn new() -> App {
         let mut items: Vec<XmlGpuEntry> = get_gpu_list().await.unwrap();
-        items.sort_by(|b, a| a.id.cmp(&b.id));
+        items.sort_by_key(|item| std::cmp::Reverse(item.id));
         let filtered_items = StatefulList::with_items(items.clone());
         App {
             all_items: items,
@@ -199,19 +290,17 @@ fn ui<B: Backend>(f: &mut Frame<B>, app: &mut App) {
         )
         .split(f.size());
 
-    let mut items: Vec<ListItem> = app
+    // Products can be listed under several series (desktop and notebook), so show the series too
+    let items: Vec<ListItem> = app
         .filtered_items
         .items
         .iter()
         .map(|i| {
-            ListItem::new(i.name.clone()).style(Style::default().fg(Color::Black).bg(Color::White))
+            ListItem::new(i.display_name())
+                .style(Style::default().fg(Color::Black).bg(Color::White))
         })
         .collect();
 
-    // Fix some items showing up twice, not all though?
-    // TODO: Figure out why this happens
-    items.dedup();
-
     // Create a List from all list items and highlight the currently selected one
     let items = List::new(items)
         .block(